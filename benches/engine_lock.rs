@@ -0,0 +1,25 @@
+//! Measures the cost of the engine's lock to the callback. Run with and without the
+//! `parking_lot` feature to compare the two locks.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rg3d_sound::engine::SoundEngine;
+use rg3d_sound_sdl::Callback;
+use sdl2::audio::AudioCallback;
+
+fn lock(c: &mut Criterion) {
+    let engine = rg3d_sound_sdl::new_engine();
+    c.bench_function("lock_unlock", |b| {
+        b.iter(|| drop(black_box(&engine).lock().unwrap()))
+    });
+}
+
+fn callback(c: &mut Criterion) {
+    let mut callback: Callback = Callback::new(rg3d_sound_sdl::new_engine());
+    let mut out = vec![0.0; SoundEngine::render_buffer_len() * 2];
+    c.bench_function("callback_block", |b| {
+        b.iter(|| callback.callback(black_box(&mut out)))
+    });
+}
+
+criterion_group!(benches, lock, callback);
+criterion_main!(benches);
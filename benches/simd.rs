@@ -0,0 +1,58 @@
+//! Compares the SIMD paths of the callback with their scalar fallbacks.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rg3d_sound_sdl::{simd, ChannelLayout};
+
+/// One block of the engine's output, in samples per channel.
+const FRAMES: usize = 4104;
+
+fn samples(len: usize) -> Vec<f32> {
+    (0..len).map(|i| (i as f32 * 0.01).sin()).collect()
+}
+
+fn f32_to_i16(c: &mut Criterion) {
+    let src = samples(FRAMES * 2);
+    let mut dst = vec![0; src.len()];
+    let mut group = c.benchmark_group("f32_to_i16");
+    group.bench_function("scalar", |b| {
+        b.iter(|| simd::scalar::f32_to_i16(black_box(&src), &mut dst))
+    });
+    group.bench_function("simd", |b| {
+        b.iter(|| simd::f32_to_i16(black_box(&src), &mut dst))
+    });
+    group.finish();
+}
+
+fn ramp_gain(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ramp_gain");
+    for channels in [2, 6, 8] {
+        let mut buf = samples(FRAMES * channels);
+        group.bench_with_input(BenchmarkId::new("scalar", channels), &channels, |b, &ch| {
+            b.iter(|| simd::scalar::ramp_gain(black_box(&mut buf), ch, 1.0, 1.0))
+        });
+        group.bench_with_input(BenchmarkId::new("simd", channels), &channels, |b, &ch| {
+            b.iter(|| simd::ramp_gain(black_box(&mut buf), ch, 1.0, 1.0))
+        });
+    }
+    group.finish();
+}
+
+fn fold_to_stereo(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fold_to_stereo");
+    for layout in [ChannelLayout::Surround51, ChannelLayout::Surround71] {
+        let gains: Vec<_> = layout.speakers().iter().map(|s| s.spread_gains()).collect();
+        let from = samples(FRAMES * gains.len());
+        let mut to = vec![(0.0, 0.0); FRAMES];
+        let channels = gains.len();
+        group.bench_with_input(BenchmarkId::new("scalar", channels), &gains, |b, gains| {
+            b.iter(|| simd::scalar::fold_to_stereo(black_box(&from), gains, &mut to))
+        });
+        group.bench_with_input(BenchmarkId::new("simd", channels), &gains, |b, gains| {
+            b.iter(|| simd::fold_to_stereo(black_box(&from), gains, &mut to))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, f32_to_i16, ramp_gain, fold_to_stereo);
+criterion_main!(benches);
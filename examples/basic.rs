@@ -1,10 +1,6 @@
 use std::{error::Error, fs::File, io::BufReader, thread, time::Duration};
 
-use rg3d_sound::{
-    buffer::{DataSource, SoundBufferResource},
-    context::SoundContext,
-    source::{generic::GenericSourceBuilder, Status},
-};
+use rg3d_sound_sdl::prelude::*;
 
 fn main() -> Result<(), Box<dyn Error>> {
     let sdl = sdl2::init()?;
@@ -0,0 +1,102 @@
+/* The C API of rg3d-sound-sdl, built with the `capi` feature. */
+
+#ifndef RG3D_SOUND_SDL_H
+#define RG3D_SOUND_SDL_H
+
+/* Generated by cbindgen from src/capi.rs. Don't edit it by hand. */
+
+#include <stdbool.h>
+#include <stdint.h>
+
+// A playback device, driving a single sound context.
+//
+// SDL's audio handles belong to the thread which opened them, so every function taking an
+// `Rg3dSound` must be called on the thread which called `rg3d_sound_open`, after the game
+// initialised SDL.
+typedef struct Rg3dSound Rg3dSound;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Opens a playback device, by name, or the default device if `device` is null, and starts it
+// playing a new sound context. Returns null on error.
+//
+// # Safety
+// `device` must be null or a valid nul-terminated string.
+struct Rg3dSound *rg3d_sound_open(const char *device);
+
+// Closes a device opened with `rg3d_sound_open`, stopping everything it was playing.
+//
+// # Safety
+// `sound` must be null or a device from `rg3d_sound_open` which hasn't been closed.
+void rg3d_sound_close(struct Rg3dSound *sound);
+
+// Checks on the device, reopening it if it has been lost. Call this once per frame.
+//
+// # Safety
+// `sound` must be a device from `rg3d_sound_open` which hasn't been closed.
+void rg3d_sound_update(struct Rg3dSound *sound);
+
+// Plays a sound file (WAV or Ogg Vorbis) without positioning it, for music and UI sounds.
+// Sounds which don't loop are removed once they finish. Returns an ID for `rg3d_sound_stop`,
+// or 0 on error.
+//
+// # Safety
+// `sound` must be a device from `rg3d_sound_open` which hasn't been closed, and `path` a valid
+// nul-terminated string.
+uint64_t rg3d_sound_play(struct Rg3dSound *sound, const char *path, bool looping);
+
+// Plays a sound file at a position in the world, heard relative to the listener, and through
+// HRTF after `rg3d_sound_load_hrtf`. Otherwise like `rg3d_sound_play`.
+//
+// # Safety
+// `sound` must be a device from `rg3d_sound_open` which hasn't been closed, `path` a valid
+// nul-terminated string, and `position` must point to three floats.
+uint64_t rg3d_sound_play_at(struct Rg3dSound *sound,
+                            const char *path,
+                            bool looping,
+                            const float *position);
+
+// Stops and removes a sound played with `rg3d_sound_play` or `rg3d_sound_play_at`. Returns
+// false if the sound has already finished.
+//
+// # Safety
+// `sound` must be a device from `rg3d_sound_open` which hasn't been closed.
+bool rg3d_sound_stop(struct Rg3dSound *sound, uint64_t id);
+
+// Moves the listener, facing along `look` with `up` pointing up, in a right-handed coordinate
+// system.
+//
+// # Safety
+// `sound` must be a device from `rg3d_sound_open` which hasn't been closed, and `position`,
+// `look` and `up` must each point to three floats.
+void rg3d_sound_set_listener(struct Rg3dSound *sound,
+                             const float *position,
+                             const float *look,
+                             const float *up);
+
+// Sets the volume of everything played, as a linear gain where 1 is unchanged. The volume
+// ramps to the new value, so it can be set every frame from a slider.
+//
+// # Safety
+// `sound` must be a device from `rg3d_sound_open` which hasn't been closed.
+void rg3d_sound_set_master_volume(struct Rg3dSound *sound, float volume);
+
+// Loads an HRIR sphere file and renders positioned sounds through HRTF with it, for players
+// wearing headphones. Returns false on error.
+//
+// # Safety
+// `sound` must be a device from `rg3d_sound_open` which hasn't been closed, and `path` a valid
+// nul-terminated string.
+bool rg3d_sound_load_hrtf(struct Rg3dSound *sound, const char *path);
+
+// Returns a description of the last error on this thread, or an empty string if there hasn't
+// been one. The string is valid until the next error on this thread.
+const char *rg3d_sound_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RG3D_SOUND_SDL_H */
@@ -0,0 +1,287 @@
+//! Using a device from async code.
+
+use std::{
+    collections::VecDeque,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, OnceLock, Weak,
+    },
+    task::{Context, Poll},
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use futures::{task::AtomicWaker, Stream};
+
+use crate::{event::CAPACITY, AudioEvent};
+
+/// The streams fed by the events of a device, shared by every sender of its events.
+#[derive(Clone, Default)]
+pub(crate) struct Streams {
+    inner: Arc<StreamsInner>,
+}
+
+#[derive(Default)]
+struct StreamsInner {
+    streams: Mutex<Vec<Weak<Shared>>>,
+}
+
+impl Drop for StreamsInner {
+    /// Ends the streams once the device and everything else that sends its events are gone.
+    fn drop(&mut self) {
+        for stream in self.streams.get_mut().unwrap().iter() {
+            if let Some(stream) = stream.upgrade() {
+                stream.waker.wake();
+            }
+        }
+    }
+}
+
+impl Streams {
+    pub(crate) fn subscribe(&self) -> AudioEvents {
+        let shared = Arc::new(Shared::default());
+        let mut streams = self.inner.streams.lock().unwrap();
+        streams.retain(|stream| stream.strong_count() > 0);
+        streams.push(Arc::downgrade(&shared));
+        AudioEvents {
+            shared,
+            senders: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Queues an event on every stream. The locks are only held long enough to push or pop an
+    /// event, so the audio thread never waits long.
+    pub(crate) fn send(&self, event: &AudioEvent) {
+        for stream in self.inner.streams.lock().unwrap().iter() {
+            if let Some(stream) = stream.upgrade() {
+                let mut queue = stream.queue.lock().unwrap();
+                if queue.len() < CAPACITY {
+                    queue.push_back(event.clone());
+                }
+                drop(queue);
+                stream.waker.wake();
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<VecDeque<AudioEvent>>,
+    waker: AtomicWaker,
+}
+
+/// A [`Stream`] of the events of a device, from [`SdlSound::event_stream`], for receiving them
+/// in an async task rather than polling [`SdlSound::events`].
+///
+/// The stream receives every event sent after it was created, alongside
+/// [`SdlSound::events`], and ends once the device has been dropped. As with
+/// [`SdlSound::events`], [`SdlSound::update`] must still be called for lost devices to be
+/// noticed, and if too many events are left waiting, new ones are dropped.
+///
+/// [`SdlSound::event_stream`]: crate::SdlSound::event_stream
+/// [`SdlSound::events`]: crate::SdlSound::events
+/// [`SdlSound::update`]: crate::SdlSound::update
+/// # Example
+/// ```no_run
+/// use futures::StreamExt;
+/// use rg3d_sound_sdl::{AudioEvent, OpenOptions};
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let sound = OpenOptions::new().open(&audio).unwrap();
+///
+/// let mut events = sound.event_stream();
+/// std::thread::spawn(move || {
+///     futures::executor::block_on(async {
+///         while let Some(event) = events.next().await {
+///             if let AudioEvent::DeviceLost { .. } = event {
+///                 eprintln!("Audio device lost");
+///             }
+///         }
+///     })
+/// });
+/// ```
+pub struct AudioEvents {
+    shared: Arc<Shared>,
+    senders: Weak<StreamsInner>,
+}
+
+impl Stream for AudioEvents {
+    type Item = AudioEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<AudioEvent>> {
+        self.shared.waker.register(cx.waker());
+        if let Some(event) = self.shared.queue.lock().unwrap().pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if self.senders.strong_count() == 0 {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// A sleep waiting to be woken by the timer thread: whether it has been, and its waker.
+type Wake = (AtomicBool, AtomicWaker);
+
+/// The thread which wakes every [`Sleep`] once its deadline has passed, started by the first.
+struct Timer {
+    /// The deadlines of the sleeps waiting to be woken. Sleeps which have been dropped are
+    /// forgotten once their deadlines pass.
+    sleeps: Mutex<Vec<(Instant, Weak<Wake>)>>,
+    changed: Condvar,
+}
+
+impl Timer {
+    fn get() -> &'static Self {
+        static TIMER: OnceLock<Timer> = OnceLock::new();
+        let mut started = false;
+        let timer = TIMER.get_or_init(|| {
+            started = true;
+            Self {
+                sleeps: Mutex::new(Vec::new()),
+                changed: Condvar::new(),
+            }
+        });
+        if started {
+            thread::Builder::new()
+                .name("rg3d-sound-sdl timer".into())
+                .spawn(move || timer.run())
+                .expect("Failed to spawn timer thread");
+        }
+        timer
+    }
+
+    fn add(&self, deadline: Instant, wake: &Arc<Wake>) {
+        let mut sleeps = self.sleeps.lock().unwrap();
+        sleeps.push((deadline, Arc::downgrade(wake)));
+        self.changed.notify_one();
+    }
+
+    fn run(&self) {
+        let mut sleeps = self.sleeps.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            sleeps.retain(|(deadline, wake)| {
+                if *deadline > now {
+                    return wake.strong_count() > 0;
+                }
+                if let Some(wake) = wake.upgrade() {
+                    wake.0.store(true, Ordering::Release);
+                    wake.1.wake();
+                }
+                false
+            });
+            sleeps = match sleeps.iter().map(|&(deadline, _)| deadline).min() {
+                Some(next) => self.changed.wait_timeout(sleeps, next - now).unwrap().0,
+                None => self.changed.wait(sleeps).unwrap(),
+            };
+        }
+    }
+}
+
+/// A future which completes at the given instant, woken by the timer thread.
+pub(crate) struct Sleep {
+    deadline: Instant,
+    woken: Option<Arc<Wake>>,
+}
+
+impl Sleep {
+    pub(crate) fn until(deadline: Instant) -> Self {
+        Self {
+            deadline,
+            woken: None,
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+        let deadline = self.deadline;
+        let woken = self.woken.get_or_insert_with(|| {
+            let woken = Arc::new((AtomicBool::new(false), AtomicWaker::new()));
+            Timer::get().add(deadline, &woken);
+            woken
+        });
+        woken.1.register(cx.waker());
+        if woken.0.load(Ordering::Acquire) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Runs `f` on a thread of its own, returning a future which completes with its result, so that
+/// slow, blocking work doesn't hold up the executor. Dropping the future before it completes
+/// waits for `f` to return, so that `f` can rely on anything the future borrows. A panic in `f`
+/// is resumed on the thread polling the future.
+pub(crate) fn spawn_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Blocking<T> {
+    let shared = Arc::new(BlockingShared {
+        result: Mutex::new(None),
+        waker: AtomicWaker::new(),
+    });
+    let thread = {
+        let shared = Arc::clone(&shared);
+        thread::Builder::new()
+            .name("rg3d-sound-sdl blocking".into())
+            .spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(f));
+                *shared.result.lock().unwrap() = Some(result);
+                shared.waker.wake();
+            })
+            .expect("Failed to spawn blocking thread")
+    };
+    Blocking {
+        shared,
+        thread: Some(thread),
+    }
+}
+
+/// The future returned by [`spawn_blocking`].
+pub(crate) struct Blocking<T> {
+    shared: Arc<BlockingShared<T>>,
+    /// The thread running the work, until its result has been taken.
+    thread: Option<JoinHandle<()>>,
+}
+
+struct BlockingShared<T> {
+    result: Mutex<Option<thread::Result<T>>>,
+    waker: AtomicWaker,
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        self.shared.waker.register(cx.waker());
+        let Some(result) = self.shared.result.lock().unwrap().take() else {
+            return Poll::Pending;
+        };
+        self.thread = None;
+        match result {
+            Ok(value) => Poll::Ready(value),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl<T> Drop for Blocking<T> {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,1263 @@
+//! The [`AudioCallback`] which feeds SDL with audio rendered by one or more [`SoundEngine`]s.
+
+use std::{
+    any::Any,
+    iter, mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use rg3d_sound::{
+    context::{SoundContext, SAMPLE_RATE},
+    engine::SoundEngine,
+    hrtf::HrirSphere,
+    renderer::{hrtf::HrtfRenderer, Renderer},
+};
+use sdl2::audio::AudioCallback;
+
+use crate::{
+    crossfade::{Crossfade, Handoff},
+    dsp::HighPass,
+    duck::Ducker,
+    event::{self, AudioEvent, EventSender},
+    fill::Filler,
+    image::StereoImage,
+    priority,
+    ramp::{self, Ramp},
+    rumble::RumbleTap,
+    telemetry,
+    tone::{Injector, Signal, TestTone},
+    varispeed::{self, Varispeed},
+    BlockRenderer, ChannelLayout, Ducking, EngineHandle, ExternalInput, InjectionMode, LevelMeter,
+    LfeMode, Loopback, LoudnessNormalizer, MixMatrix, NetworkSink, Rumble, TestSignal,
+    UnderrunFill,
+};
+
+/// A high-pass cutoff frequency, in Hz, suitable for removing DC offset with
+/// [`Callback::set_high_pass`].
+pub const DEFAULT_HIGH_PASS: f32 = 20.0;
+
+/// How long changes to the gains of a [`Callback`] take by default, long enough not to click but
+/// short enough to feel immediate.
+pub const DEFAULT_GAIN_RAMP: Duration = Duration::from_millis(20);
+
+/// Identifies an engine attached to a [`Callback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EngineId(usize);
+
+impl EngineId {
+    /// The id of the engine passed to [`Callback::new`].
+    pub const MAIN: Self = Self(0);
+}
+
+/// An engine attached to a [`Callback`], along with how its output is mixed.
+struct Attached<R> {
+    id: EngineId,
+    engine: R,
+    gain: Ramp,
+    /// The routing set with [`Callback::set_routing`], if any.
+    routing: Option<MixMatrix>,
+    /// The meter set with [`Callback::set_meter`], if any.
+    meter: Option<LevelMeter>,
+    /// How far the engine has faded in, if it replaced another.
+    fade_in: Option<Crossfade>,
+    /// Engines this one replaced which are still fading out.
+    outgoing: Vec<(R, Crossfade)>,
+}
+
+impl<R: BlockRenderer> Attached<R> {
+    /// Renders the engine, along with any engines it is replacing, and mixes it into `mix`,
+    /// scaling each frame by the gains in `ducking`, if any.
+    fn mix_into(
+        &mut self,
+        scratch: &mut [(f32, f32)],
+        mix: &mut [f32],
+        default_routing: &MixMatrix,
+        ducking: Option<&[f32]>,
+    ) {
+        let routing = self.routing.as_ref().unwrap_or(default_routing);
+        self.engine.render(scratch);
+        if let Some(fade) = &mut self.fade_in {
+            fade.apply(scratch, Crossfade::level_in);
+            if fade.is_finished() {
+                self.fade_in = None;
+            }
+        }
+        duck(scratch, ducking);
+        // Engines being replaced ramp their gain in step with this one.
+        let gain = self.gain;
+        self.gain.apply_stereo(scratch);
+        if let Some(meter) = &self.meter {
+            meter.measure(scratch);
+        }
+        routing.mix_into(scratch, mix, 1.0);
+        for (engine, fade) in &mut self.outgoing {
+            engine.render(scratch);
+            fade.apply(scratch, Crossfade::level_out);
+            duck(scratch, ducking);
+            let mut gain = gain;
+            gain.apply_stereo(scratch);
+            routing.mix_into(scratch, mix, 1.0);
+        }
+        self.outgoing.retain(|(_, fade)| !fade.is_finished());
+    }
+}
+
+/// Scales each frame by the matching gain, if there are any.
+fn duck(frames: &mut [(f32, f32)], gains: Option<&[f32]>) {
+    if let Some(gains) = gains {
+        for (frame, gain) in frames.iter_mut().zip(gains) {
+            frame.0 *= gain;
+            frame.1 *= gain;
+        }
+    }
+}
+
+/// An [`AudioCallback`] used to feed the SDL audio device with rendered audio from a
+/// [`SoundEngine`]
+///
+/// More engines can be attached with [`Callback::attach`], for example to pause game audio and
+/// a media player independently. Each engine is rendered separately and the results are summed,
+/// scaled by a per-engine gain. Audio from outside `rg3d_sound` can be mixed in too, by adding an
+/// [`ExternalInput`] with [`Callback::add_input`].
+///
+/// Engines are rendered through the [`BlockRenderer`] trait, so a callback can also play audio
+/// from other renderers.
+///
+/// On devices with more than two channels, each engine's stereo output is routed to the device's
+/// speakers with a [`MixMatrix`], set with [`Callback::set_routing`]. Engines without a routing of
+/// their own play through the front left and right speakers.
+pub struct Callback<R: BlockRenderer = EngineHandle> {
+    engines: Vec<Attached<R>>,
+    inputs: Vec<ExternalInput>,
+    loopbacks: Vec<Loopback>,
+    rumbles: Vec<RumbleTap>,
+    network_sinks: Vec<NetworkSink>,
+    next_id: usize,
+    layout: ChannelLayout,
+    /// The routing of engines that don't have their own, and of external inputs.
+    default_routing: MixMatrix,
+    /// How the output is folded down to stereo for loopbacks and network sinks.
+    downmix: MixMatrix,
+    /// The most recently mixed block of interleaved output, which is copied to SDL's buffers.
+    mix: Vec<f32>,
+    /// The number of samples of `mix` which have already been copied to SDL.
+    mix_pos: usize,
+    /// Scratch space each engine is rendered into before being mixed.
+    scratch: Vec<(f32, f32)>,
+    /// The external inputs of the current block, mixed before the engines in case one of them
+    /// triggers ducking.
+    input_mix: Vec<(f32, f32)>,
+    /// The level the engines are currently faded to.
+    fade: f32,
+    /// An action to run once the engines have faded out, after which they fade back in.
+    pending: Option<Box<dyn FnOnce() + Send>>,
+    high_pass: Option<HighPass>,
+    loudness: Option<LoudnessNormalizer>,
+    ducker: Option<Ducker>,
+    playback_rate: f32,
+    /// The ratio of the engine's rate to the rate the driver plays the device at.
+    rate_correction: f32,
+    varispeed: Varispeed,
+    /// Resamples the finished output to the device's rate, so that everything before it runs at
+    /// [`SAMPLE_RATE`].
+    resampler: Varispeed,
+    image: StereoImage,
+    test_tone: Option<TestTone>,
+    /// The test signal set with [`Callback::set_test_signal`], if any, and how it's injected.
+    injector: Option<Injector>,
+    injection_mode: InjectionMode,
+    events: Option<EventSender>,
+    /// When the previous buffer was requested, for detecting underruns.
+    last_callback: Option<Instant>,
+    /// Set when playback resumes, so that the time spent paused isn't taken as an underrun.
+    resync: Arc<AtomicBool>,
+    /// Whether the last block panicked, so that a panic is reported once rather than every block.
+    panicking: bool,
+    underrun_fill: UnderrunFill,
+    filler: Filler,
+    /// The number of times SDL has asked for audio, for noticing when it stops.
+    heartbeat: Arc<AtomicU64>,
+    /// How long changes to gains take.
+    gain_ramp: Duration,
+    /// The gain applied to the mix before it is sent anywhere.
+    master: Ramp,
+    /// The gain applied to everything sent to the device.
+    trim: Ramp,
+    /// The gain fading the device out to silence while muted.
+    mute: Ramp,
+    /// The device this callback used to play to, which is fading out a copy of the output.
+    handoff_in: Option<(Handoff, Crossfade)>,
+    /// Set on the callback left on a replaced device, which plays only what it is handed.
+    handoff_out: Option<(Handoff, Crossfade)>,
+    /// Set when the next callback should try to give the audio thread real-time priority.
+    elevate: bool,
+    realtime: bool,
+}
+
+impl<R: BlockRenderer> Callback<R> {
+    /// Create a new `Callback` from an existing engine. A [`SoundEngine`] must be opened with
+    /// [`new_engine`][crate::new_engine] so that the manual rendering functions can be used. The
+    /// engine's id is [`EngineId::MAIN`].
+    pub fn new(engine: R) -> Self {
+        let mut callback = Self {
+            engines: Vec::new(),
+            inputs: Vec::new(),
+            loopbacks: Vec::new(),
+            rumbles: Vec::new(),
+            network_sinks: Vec::new(),
+            next_id: 0,
+            layout: ChannelLayout::Stereo,
+            default_routing: MixMatrix::front(ChannelLayout::Stereo),
+            downmix: MixMatrix::downmix(ChannelLayout::Stereo, LfeMode::Discard),
+            mix: Vec::new(),
+            mix_pos: 0,
+            scratch: Vec::new(),
+            input_mix: Vec::new(),
+            fade: 1.0,
+            pending: None,
+            high_pass: None,
+            loudness: None,
+            ducker: None,
+            playback_rate: 1.0,
+            rate_correction: 1.0,
+            varispeed: Varispeed::default(),
+            resampler: Varispeed::default(),
+            image: StereoImage::default(),
+            test_tone: None,
+            injector: None,
+            injection_mode: InjectionMode::default(),
+            events: None,
+            last_callback: None,
+            resync: Arc::new(AtomicBool::new(false)),
+            panicking: false,
+            underrun_fill: UnderrunFill::default(),
+            filler: Filler::default(),
+            heartbeat: Arc::new(AtomicU64::new(0)),
+            gain_ramp: DEFAULT_GAIN_RAMP,
+            master: Ramp::new(1.0),
+            trim: Ramp::new(1.0),
+            mute: Ramp::new(1.0),
+            handoff_in: None,
+            handoff_out: None,
+            elevate: false,
+            realtime: false,
+        };
+        callback.attach(engine, 1.0);
+        callback
+    }
+
+    /// Attaches another engine to this callback, whose output will be mixed in at the given
+    /// `gain`. Like the engine passed to [`Callback::new`], it must be opened with
+    /// [`new_engine`][crate::new_engine]. Returns an id which can be used to adjust or detach the
+    /// engine later.
+    /// # Example
+    /// ```no_run
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let (game, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+    ///
+    /// let media = rg3d_sound_sdl::new_engine();
+    /// let media_id = device.lock().attach(media.clone(), 0.8);
+    /// device.resume();
+    /// ```
+    pub fn attach(&mut self, engine: R, gain: f32) -> EngineId {
+        let id = EngineId(self.next_id);
+        self.next_id += 1;
+        self.engines.push(Attached {
+            id,
+            engine,
+            gain: Ramp::new(gain),
+            routing: None,
+            meter: None,
+            fade_in: None,
+            outgoing: Vec::new(),
+        });
+        id
+    }
+
+    /// Replaces an attached engine with another, crossfading from one to the other over
+    /// `crossfade` as the device plays, without a gap. The new engine keeps the id, gain and
+    /// routing of the old one, and the old one is dropped once it has faded out. Does nothing if
+    /// no engine with the given id is attached.
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+    /// let media = rg3d_sound_sdl::new_engine();
+    /// let media_id = device.lock().attach(media, 1.0);
+    /// device.resume();
+    ///
+    /// // Loading a saved game:
+    /// let loaded = rg3d_sound_sdl::new_engine();
+    /// device
+    ///     .lock()
+    ///     .replace_engine(media_id, loaded, Duration::from_millis(100));
+    /// ```
+    pub fn replace_engine(&mut self, id: EngineId, engine: R, crossfade: Duration) {
+        let attached = match self.engines.iter_mut().find(|a| a.id == id) {
+            Some(attached) => attached,
+            None => return,
+        };
+        let level = attached.fade_in.map_or(1.0, |fade| fade.level_in());
+        let fade = Crossfade::new(crossfade, level);
+        let old = mem::replace(&mut attached.engine, engine);
+        if fade.is_finished() {
+            attached.fade_in = None;
+            attached.outgoing.clear();
+        } else {
+            attached.fade_in = Some(fade);
+            attached.outgoing.push((old, fade));
+        }
+    }
+
+    /// Detaches an engine from this callback, returning its handle, or `None` if no engine with
+    /// the given id is attached.
+    pub fn detach(&mut self, id: EngineId) -> Option<R> {
+        let index = self.engines.iter().position(|a| a.id == id)?;
+        Some(self.engines.remove(index).engine)
+    }
+
+    /// Sets the gain an attached engine is mixed at, ramping to it over the
+    /// [gain ramp][Callback::set_gain_ramp]. Does nothing if no engine with the given id is
+    /// attached.
+    pub fn set_gain(&mut self, id: EngineId, gain: f32) {
+        let frames = ramp::frames(self.gain_ramp);
+        if let Some(attached) = self.engines.iter_mut().find(|a| a.id == id) {
+            attached.gain.set(gain, frames);
+        }
+    }
+
+    /// Returns the gain an attached engine is mixed at, or `None` if no engine with the given id
+    /// is attached.
+    pub fn gain(&self, id: EngineId) -> Option<f32> {
+        self.engines
+            .iter()
+            .find(|a| a.id == id)
+            .map(|a| a.gain.target())
+    }
+
+    /// Sets the [`LevelMeter`] measuring an attached engine, or with `None`, stops measuring it.
+    /// Does nothing if no engine with the given id is attached.
+    pub fn set_meter(&mut self, id: EngineId, meter: impl Into<Option<LevelMeter>>) {
+        if let Some(attached) = self.engines.iter_mut().find(|a| a.id == id) {
+            attached.meter = meter.into();
+        }
+    }
+
+    /// Returns the meter measuring an attached engine, or `None` if it isn't metered or no engine
+    /// with the given id is attached.
+    pub fn meter(&self, id: EngineId) -> Option<&LevelMeter> {
+        self.engines
+            .iter()
+            .find(|a| a.id == id)
+            .and_then(|a| a.meter.as_ref())
+    }
+
+    /// Returns an attached engine, or `None` if no engine with the given id is attached.
+    pub fn engine(&self, id: EngineId) -> Option<&R> {
+        self.engines.iter().find(|a| a.id == id).map(|a| &a.engine)
+    }
+
+    /// Returns an attached engine mutably, or `None` if no engine with the given id is attached.
+    pub fn engine_mut(&mut self, id: EngineId) -> Option<&mut R> {
+        self.engines
+            .iter_mut()
+            .find(|a| a.id == id)
+            .map(|a| &mut a.engine)
+    }
+
+    /// Returns the ids of the attached engines, in the order they were attached.
+    pub fn engine_ids(&self) -> impl Iterator<Item = EngineId> + '_ {
+        self.engines.iter().map(|a| a.id)
+    }
+
+    /// Sets the channel layout of the device this callback plays to. This must match the number
+    /// of channels the device was opened with, and is set automatically by
+    /// [`OpenOptions::open`][crate::OpenOptions::open]. Changing the layout clears the routing of
+    /// every engine, and restores the default [downmix][Callback::set_downmix].
+    pub fn set_channel_layout(&mut self, layout: ChannelLayout) {
+        self.layout = layout;
+        self.default_routing = MixMatrix::front(layout);
+        self.downmix = MixMatrix::downmix(layout, LfeMode::Discard);
+        for attached in &mut self.engines {
+            attached.routing = None;
+        }
+        self.mix.clear();
+        self.mix_pos = 0;
+        self.varispeed.clear();
+        self.resampler.clear();
+    }
+
+    /// Returns the channel layout of the device this callback plays to.
+    pub fn channel_layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
+    /// Sets how the output of an attached engine is routed to the device's channels, or with
+    /// `None`, restores the default of playing through the front left and right speakers. Does
+    /// nothing if no engine with the given id is attached.
+    /// # Panics
+    /// This function will panic if the matrix doesn't have the same number of channels as the
+    /// [channel layout][Callback::channel_layout].
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound::context::SoundContext;
+    /// use rg3d_sound_sdl::{ChannelLayout, MixMatrix, OpenOptions};
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = OpenOptions::new()
+    ///     .channel_layout(ChannelLayout::Surround51)
+    ///     .open(&audio)
+    ///     .unwrap();
+    ///
+    /// let music = SoundContext::new();
+    /// let sfx = SoundContext::new();
+    /// let mut callback = sound.device_mut().lock();
+    /// callback.attach_context(music.clone(), 1.0);
+    /// let sfx_id = callback.attach_context(sfx.clone(), 1.0);
+    /// callback.set_routing(sfx_id, MixMatrix::spread(ChannelLayout::Surround51));
+    /// ```
+    pub fn set_routing(&mut self, id: EngineId, routing: impl Into<Option<MixMatrix>>) {
+        let routing = routing.into();
+        if let Some(routing) = &routing {
+            assert_eq!(
+                routing.channels(),
+                self.layout.channels() as usize,
+                "Routing must have one entry per channel"
+            );
+        }
+        if let Some(attached) = self.engines.iter_mut().find(|a| a.id == id) {
+            attached.routing = routing;
+        }
+    }
+
+    /// Returns the routing set for an attached engine, or `None` if it uses the default routing or
+    /// no engine with the given id is attached.
+    pub fn routing(&self, id: EngineId) -> Option<&MixMatrix> {
+        self.engines
+            .iter()
+            .find(|a| a.id == id)
+            .and_then(|a| a.routing.as_ref())
+    }
+
+    /// Sets how the output is folded down to stereo for [loopbacks][Callback::add_loopback] and
+    /// [network sinks][Callback::add_network_sink]. Defaults to
+    /// [`MixMatrix::downmix`] with [`LfeMode::Discard`].
+    /// # Panics
+    /// This function will panic if the matrix doesn't have the same number of channels as the
+    /// [channel layout][Callback::channel_layout].
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::{ChannelLayout, LfeMode, MixMatrix, OpenOptions};
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = OpenOptions::new()
+    ///     .channel_layout(ChannelLayout::Surround51)
+    ///     .open(&audio)
+    ///     .unwrap();
+    ///
+    /// let mut downmix = MixMatrix::downmix(ChannelLayout::Surround51, LfeMode::Mix(-10.0));
+    /// downmix.normalize();
+    /// sound.device_mut().lock().set_downmix(downmix);
+    /// ```
+    pub fn set_downmix(&mut self, downmix: MixMatrix) {
+        assert_eq!(
+            downmix.channels(),
+            self.layout.channels() as usize,
+            "Downmix must have one entry per channel"
+        );
+        self.downmix = downmix;
+    }
+
+    /// Returns how the output is folded down to stereo.
+    pub fn downmix(&self) -> &MixMatrix {
+        &self.downmix
+    }
+
+    /// Sets the cutoff frequency, in Hz, of a high-pass filter applied to the output, or with `None`,
+    /// removes the filter. [`DEFAULT_HIGH_PASS`] removes DC offset, for example from
+    /// procedurally generated sources, without affecting anything audible. Cutoffs above half the
+    /// sample rate are clamped to it, and cutoffs which aren't above 0, or are NaN, remove the
+    /// filter as `None` does.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::DEFAULT_HIGH_PASS;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+    /// device.lock().set_high_pass(DEFAULT_HIGH_PASS);
+    /// device.resume();
+    /// ```
+    pub fn set_high_pass(&mut self, cutoff: impl Into<Option<f32>>) {
+        self.high_pass = cutoff
+            .into()
+            .filter(|&cutoff| cutoff > 0.0)
+            .map(|cutoff| HighPass::new(cutoff.min(SAMPLE_RATE as f32 / 2.0)));
+    }
+
+    /// Returns the cutoff frequency of the high-pass filter applied to the output, if any.
+    pub fn high_pass(&self) -> Option<f32> {
+        self.high_pass.as_ref().map(HighPass::cutoff)
+    }
+
+    /// Sets the [`LoudnessNormalizer`] applied to the output, or with `None`, stops normalizing it.
+    pub fn set_loudness_normalizer(&mut self, normalizer: impl Into<Option<LoudnessNormalizer>>) {
+        self.loudness = normalizer.into();
+    }
+
+    /// Returns the [`LoudnessNormalizer`] applied to the output, if any.
+    pub fn loudness_normalizer(&self) -> Option<&LoudnessNormalizer> {
+        self.loudness.as_ref()
+    }
+
+    /// Returns the [`LoudnessNormalizer`] applied to the output mutably, if any.
+    pub fn loudness_normalizer_mut(&mut self) -> Option<&mut LoudnessNormalizer> {
+        self.loudness.as_mut()
+    }
+
+    /// Sets how some engines are ducked whenever another engine or an external input plays, or
+    /// with `None`, stops ducking.
+    pub fn set_ducking(&mut self, ducking: impl Into<Option<Ducking>>) {
+        let gain = self.ducker.as_ref().map_or(1.0, Ducker::gain);
+        match ducking.into() {
+            Some(ducking) => self.ducker = Some(Ducker::new(ducking, gain)),
+            // The ducked engines recover over the release time, rather than jumping back up.
+            None => {
+                if let Some(ducker) = &mut self.ducker {
+                    ducker.retire();
+                }
+            }
+        }
+    }
+
+    /// Returns how engines are ducked, if they are.
+    pub fn ducking(&self) -> Option<&Ducking> {
+        self.ducker
+            .as_ref()
+            .filter(|ducker| !ducker.is_retiring())
+            .map(|ducker| &ducker.ducking)
+    }
+
+    /// Sets the rate every attached engine plays at, like changing the speed of a tape, so that
+    /// slowing the game down for bullet time slows and lowers all of its audio together. The rate
+    /// is clamped to between 0.25 and 2, and defaults to 1. External inputs and test tones play
+    /// at the normal rate.
+    /// # Example
+    /// ```no_run
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+    /// device.resume();
+    ///
+    /// // Entering bullet time:
+    /// device.lock().set_playback_rate(0.5);
+    /// ```
+    pub fn set_playback_rate(&mut self, rate: f32) {
+        self.playback_rate = rate.clamp(varispeed::MIN_RATE, varispeed::MAX_RATE);
+    }
+
+    /// Returns the rate every attached engine plays at.
+    pub fn playback_rate(&self) -> f32 {
+        self.playback_rate
+    }
+
+    /// Resamples the output so that it plays at the right pitch and speed on a device which the
+    /// driver plays at `rate`, in Hz, rather than the engine's rate.
+    pub(crate) fn set_device_rate(&mut self, rate: u32) {
+        self.rate_correction = SAMPLE_RATE as f32 / rate as f32;
+    }
+
+    /// Returns the rate the output is resampled to, in Hz.
+    pub(crate) fn device_rate(&self) -> u32 {
+        (SAMPLE_RATE as f32 / self.rate_correction).round() as u32
+    }
+
+    /// Replaces the output with a sine wave of the given frequency, in Hz, for `duration`, for
+    /// example to test each speaker from an audio settings screen. The tone plays only on the
+    /// given channel, or on every channel with `None`, and every other channel is silent. Playing
+    /// another tone replaces the current one.
+    /// # Panics
+    /// This function will panic if `channel` is not a channel of the
+    /// [channel layout][Callback::channel_layout].
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+    /// device.resume();
+    ///
+    /// // The "Test" button for the right speaker.
+    /// device.lock().play_test_tone(1, 440.0, Duration::from_secs(1));
+    /// ```
+    pub fn play_test_tone(
+        &mut self,
+        channel: impl Into<Option<usize>>,
+        frequency: f32,
+        duration: Duration,
+    ) {
+        self.play_test_signal(Signal::Sine(frequency), channel.into(), duration);
+    }
+
+    /// Replaces the output with white noise for `duration`, like [`Callback::play_test_tone`].
+    /// # Panics
+    /// This function will panic if `channel` is not a channel of the
+    /// [channel layout][Callback::channel_layout].
+    pub fn play_test_noise(&mut self, channel: impl Into<Option<usize>>, duration: Duration) {
+        self.play_test_signal(Signal::Noise, channel.into(), duration);
+    }
+
+    /// Stops the test tone, if one is playing, and restores the normal output.
+    pub fn stop_test_tone(&mut self) {
+        self.test_tone = None;
+    }
+
+    /// Returns whether a test tone is playing.
+    pub fn is_playing_test_tone(&self) -> bool {
+        self.test_tone.is_some()
+    }
+
+    fn play_test_signal(&mut self, signal: Signal, channel: Option<usize>, duration: Duration) {
+        if let Some(channel) = channel {
+            assert!(
+                channel < self.layout.channels() as usize,
+                "Test tone channel out of range"
+            );
+        }
+        self.test_tone = Some(TestTone::new(signal, channel, duration));
+    }
+
+    /// Injects a deterministic [`TestSignal`] where the output of the engines and external inputs
+    /// would be, or with `None`, stops injecting it, so that automated tests can check the whole
+    /// chain from there to the hardware, including the channel layout and clipping. Everything
+    /// after the engines still applies to the signal, such as the master gain and the
+    /// [stereo image][Callback::set_balance]. Setting a signal starts it from the beginning.
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use rg3d_sound_sdl::{InjectionMode, TestSignal};
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+    /// device.resume();
+    ///
+    /// let mut callback = device.lock();
+    /// callback.set_test_signal_mode(InjectionMode::Replace);
+    /// callback.set_test_signal(TestSignal::ChannelSweep {
+    ///     dwell: Duration::from_millis(500),
+    /// });
+    /// ```
+    pub fn set_test_signal(&mut self, signal: impl Into<Option<TestSignal>>) {
+        self.injector = signal.into().map(Injector::new);
+    }
+
+    /// Returns the test signal being injected, if any.
+    pub fn test_signal(&self) -> Option<TestSignal> {
+        self.injector.as_ref().map(Injector::signal)
+    }
+
+    /// Sets whether the test signal replaces the output of the engines or is mixed with it.
+    /// Defaults to [`InjectionMode::Replace`].
+    pub fn set_test_signal_mode(&mut self, mode: InjectionMode) {
+        self.injection_mode = mode;
+    }
+
+    /// Returns whether the test signal replaces the output of the engines or is mixed with it.
+    pub fn test_signal_mode(&self) -> InjectionMode {
+        self.injection_mode
+    }
+
+    /// Adds an [`ExternalInput`] to be mixed into the output. Adding an input that has already
+    /// been added does nothing.
+    pub fn add_input(&mut self, input: ExternalInput) {
+        if !self.inputs.iter().any(|i| i.ptr_eq(&input)) {
+            self.inputs.push(input);
+        }
+    }
+
+    /// Stops mixing an [`ExternalInput`] into the output.
+    pub fn remove_input(&mut self, input: &ExternalInput) {
+        self.inputs.retain(|i| !i.ptr_eq(input));
+    }
+
+    /// Adds a [`Loopback`] tap, which will receive a copy of the final mixed output. Adding a tap
+    /// that has already been added does nothing.
+    pub fn add_loopback(&mut self, loopback: Loopback) {
+        if !self.loopbacks.iter().any(|l| l.ptr_eq(&loopback)) {
+            self.loopbacks.push(loopback);
+        }
+    }
+
+    /// Returns the external inputs mixed into the output.
+    pub fn inputs(&self) -> &[ExternalInput] {
+        &self.inputs
+    }
+
+    /// Returns the [`Loopback`] taps the output is sent to.
+    pub fn loopbacks(&self) -> &[Loopback] {
+        &self.loopbacks
+    }
+
+    /// Stops sending the output to a [`Loopback`] tap.
+    pub fn remove_loopback(&mut self, loopback: &Loopback) {
+        self.loopbacks.retain(|l| !l.ptr_eq(loopback));
+    }
+
+    /// Adds a [`Rumble`] tap, which will measure the low frequencies of the final mixed output.
+    /// Adding a tap that has already been added does nothing.
+    pub fn add_rumble(&mut self, rumble: Rumble) {
+        if !self.rumbles.iter().any(|r| r.rumble().ptr_eq(&rumble)) {
+            self.rumbles.push(RumbleTap::new(rumble));
+        }
+    }
+
+    /// Stops measuring the output with a [`Rumble`] tap.
+    pub fn remove_rumble(&mut self, rumble: &Rumble) {
+        self.rumbles.retain(|r| !r.rumble().ptr_eq(rumble));
+    }
+
+    /// Adds a [`NetworkSink`], which will stream the final mixed output. Adding a sink that has
+    /// already been added does nothing.
+    pub fn add_network_sink(&mut self, sink: NetworkSink) {
+        if !self.network_sinks.iter().any(|s| s.ptr_eq(&sink)) {
+            self.network_sinks.push(sink);
+        }
+    }
+
+    /// Stops streaming the output to a [`NetworkSink`].
+    pub fn remove_network_sink(&mut self, sink: &NetworkSink) {
+        self.network_sinks.retain(|s| !s.ptr_eq(sink));
+    }
+
+    /// Sets a gain applied to everything sent to the device, after every other stage, to make up
+    /// for differences in loudness between devices. Changes are ramped over the
+    /// [gain ramp][Callback::set_gain_ramp]. Defaults to 1.
+    /// [`SdlSound::set_device_trim`][crate::SdlSound::set_device_trim] sets this automatically for
+    /// each device.
+    pub fn set_output_trim(&mut self, gain: f32) {
+        self.trim.set(gain, ramp::frames(self.gain_ramp));
+    }
+
+    /// Returns the gain applied to everything sent to the device.
+    pub fn output_trim(&self) -> f32 {
+        self.trim.target()
+    }
+
+    /// Sets the master gain, applied to the whole mix after loudness normalization, before it is
+    /// copied to loopbacks and network sinks. Unlike the master gain of an engine, which steps
+    /// from one block to the next, changes are ramped over the
+    /// [gain ramp][Callback::set_gain_ramp], so that moving a volume slider doesn't crackle.
+    /// Defaults to 1.
+    pub fn set_master_gain(&mut self, gain: f32) {
+        self.master.set(gain, ramp::frames(self.gain_ramp));
+    }
+
+    /// Returns the master gain.
+    pub fn master_gain(&self) -> f32 {
+        self.master.target()
+    }
+
+    /// Fades everything sent to the device out to silence over `fade`, or back in, for example
+    /// while the game is in the background. Unlike the master gain, muting also silences audio
+    /// mixed before the call but not yet played, so it takes effect straight away, and it doesn't
+    /// change what is copied to loopbacks and network sinks.
+    pub fn set_muted(&mut self, muted: bool, fade: Duration) {
+        let gain = if muted { 0.0 } else { 1.0 };
+        self.mute.set(gain, ramp::frames(fade));
+    }
+
+    /// Returns whether the output is muted, or fading out to silence.
+    pub fn is_muted(&self) -> bool {
+        self.mute.target() == 0.0
+    }
+
+    /// Sets how long changes to the gains of this callback take to ramp to their new values: the
+    /// master gain, the output trim, the gains of engines and external inputs, and the balance,
+    /// mono downmix and channel swap. Changes that are already ramping carry on at their old
+    /// rate. Defaults to [`DEFAULT_GAIN_RAMP`].
+    pub fn set_gain_ramp(&mut self, ramp: Duration) {
+        self.gain_ramp = ramp;
+    }
+
+    /// Returns how long changes to gains take to ramp.
+    pub fn gain_ramp(&self) -> Duration {
+        self.gain_ramp
+    }
+
+    /// Sets what is played where audio is missing: when rendering a block panics, when an
+    /// external input runs dry, or when the device this callback played to before being switched
+    /// runs out of audio to fade out. Defaults to [`UnderrunFill::Silence`].
+    pub fn set_underrun_fill(&mut self, fill: UnderrunFill) {
+        self.underrun_fill = fill.clamped();
+    }
+
+    /// Returns what is played where audio is missing.
+    pub fn underrun_fill(&self) -> UnderrunFill {
+        self.underrun_fill
+    }
+
+    /// Sets the balance between the left and right speakers, from -1 for fully left to 1 for fully
+    /// right, for example from an accessibility setting. Balance follows a constant-power law, so
+    /// the centre, 0, leaves the output alone and either side is turned up by 3dB. Test tones
+    /// aren't affected, and neither are the centre and low frequency speakers, or loopbacks and
+    /// network sinks. Changes are ramped over the time set with [`Callback::set_gain_ramp`].
+    pub fn set_balance(&mut self, balance: f32) {
+        self.image.balance = balance.clamp(-1.0, 1.0);
+    }
+
+    /// Returns the balance between the left and right speakers.
+    pub fn balance(&self) -> f32 {
+        self.image.balance
+    }
+
+    /// Sets whether the left and right speakers both play the sum of the two, turned down by 3dB,
+    /// for players who can only hear from one side. On surround devices, each pair of left and
+    /// right speakers is folded separately. The balance is applied afterwards, and the switch is
+    /// crossfaded over the time set with [`Callback::set_gain_ramp`].
+    pub fn set_mono_downmix(&mut self, mono: bool) {
+        self.image.mono = mono;
+    }
+
+    /// Returns whether the left and right speakers are folded to mono.
+    pub fn is_mono_downmix(&self) -> bool {
+        self.image.mono
+    }
+
+    /// Sets whether the left and right channels are swapped, for headsets worn back to front or
+    /// speakers wired the wrong way round. On surround devices, each pair of left and right
+    /// speakers is swapped. The switch is crossfaded over the time set with
+    /// [`Callback::set_gain_ramp`].
+    pub fn swap_channels(&mut self, swapped: bool) {
+        self.image.swapped = swapped;
+    }
+
+    /// Returns whether the left and right channels are swapped.
+    pub fn is_swapping_channels(&self) -> bool {
+        self.image.swapped
+    }
+
+    /// Returns whether the audio thread is known to be running with real-time priority, after
+    /// the device was opened with [`OpenOptions::realtime_priority`][crate::OpenOptions::realtime_priority]
+    /// and has played at least one buffer. This can only be checked with the `realtime` feature on
+    /// Unix, and is `false` otherwise, even if SDL raised the priority itself.
+    pub fn is_realtime(&self) -> bool {
+        self.realtime
+    }
+}
+
+impl Callback<EngineHandle> {
+    /// Switches every context of every attached engine to HRTF rendering with the given sphere,
+    /// or with `None`, back to the default panning renderer. To avoid clicks, the engines are
+    /// faded out and back in around the switch, taking about two blocks of playback. HRTF only
+    /// applies to mono sources; stereo sources are always panned.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound::{context::SAMPLE_RATE, hrtf::HrirSphere};
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+    /// device.resume();
+    ///
+    /// let headphone_mode = true;
+    /// let sphere = HrirSphere::from_file("IRC_1002_C.bin", SAMPLE_RATE).unwrap();
+    /// device.lock().set_hrtf(headphone_mode.then(|| sphere));
+    /// ```
+    pub fn set_hrtf(&mut self, sphere: Option<HrirSphere>) {
+        let contexts: Vec<_> = self
+            .engines
+            .iter()
+            .flat_map(|a| a.engine.lock().unwrap().contexts().to_vec())
+            .collect();
+        self.fade_through(move || {
+            for context in contexts {
+                let renderer = match &sphere {
+                    Some(sphere) => Renderer::HrtfRenderer(HrtfRenderer::new(sphere.clone())),
+                    None => Renderer::Default,
+                };
+                context.state().set_renderer(renderer);
+            }
+        });
+    }
+
+    /// Attaches a context in an engine of its own, so that it can be given its own gain and
+    /// routing. Returns the id of the new engine.
+    pub fn attach_context(&mut self, context: SoundContext, gain: f32) -> EngineId {
+        let engine = crate::new_engine();
+        engine.lock().unwrap().add_context(context);
+        self.attach(engine, gain)
+    }
+}
+
+impl<R: BlockRenderer> Callback<R> {
+    /// Sets where [`AudioEvent`]s from the audio thread are sent.
+    pub(crate) fn set_events(&mut self, events: EventSender) {
+        self.events = Some(events);
+    }
+
+    /// Returns a flag which, once set, makes the next callback forget when the previous one ran.
+    pub(crate) fn resync_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.resync)
+    }
+
+    /// Returns the count of times SDL has asked this callback for audio.
+    pub(crate) fn heartbeat(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.heartbeat)
+    }
+
+    /// Makes the next callback try to give the thread it runs on real-time priority, or stops
+    /// trying.
+    pub(crate) fn request_realtime(&mut self, realtime: bool) {
+        self.elevate = realtime;
+        self.realtime = false;
+    }
+
+    /// Applies the output trim and muting to a buffer about to be sent to the device.
+    fn apply_trim(&mut self, out: &mut [f32]) {
+        self.trim.apply(out, self.layout.channels() as usize);
+        self.mute.apply(out, self.layout.channels() as usize);
+    }
+
+    /// Starts handing a copy of the output to the device this callback is being moved away from,
+    /// beginning with what has been mixed but not played, and fading in over `crossfade`.
+    pub(crate) fn begin_handoff(&mut self, handoff: Handoff, crossfade: Duration) {
+        handoff.push(&self.mix[self.mix_pos..]);
+        self.handoff_in = Some((handoff, Crossfade::new(crossfade, 1.0)));
+    }
+
+    /// Makes this callback play only what it is handed, fading it out over `crossfade`, without
+    /// rendering anything.
+    pub(crate) fn play_handoff(&mut self, handoff: Handoff, crossfade: Duration) {
+        self.handoff_out = Some((handoff, Crossfade::new(crossfade, 1.0)));
+    }
+
+    fn send(&self, event: AudioEvent) {
+        if let Some(events) = &self.events {
+            event::send(events, event);
+        }
+    }
+
+    /// Reports an underrun if much more time has passed since the previous callback than it
+    /// took to play the buffer it filled.
+    fn check_underrun(&mut self, now: Instant, duration: Duration) {
+        if self.resync.swap(false, Ordering::Relaxed) {
+            self.last_callback = None;
+        }
+        if let Some(last) = self.last_callback.replace(now) {
+            let gap = now - last;
+            if gap > duration * 2 {
+                telemetry::underrun();
+                self.send(AudioEvent::Underrun { gap });
+            }
+        }
+    }
+
+    /// Mixes the next block, filling it as the [`UnderrunFill`] says instead if rendering panics,
+    /// as unwinding into SDL would abort the process.
+    fn mix_block_or_fill(&mut self) {
+        let channels = self.layout.channels() as usize;
+        match panic::catch_unwind(AssertUnwindSafe(|| self.mix_block())) {
+            Ok(()) => {
+                self.panicking = false;
+                let samples = self.mix.iter().copied();
+                self.filler.record(self.underrun_fill, samples, channels);
+            }
+            Err(payload) => {
+                self.mix.clear();
+                self.mix
+                    .resize(SoundEngine::render_buffer_len() * channels, 0.0);
+                self.filler
+                    .fill(self.underrun_fill, &mut self.mix, channels);
+                if !self.panicking {
+                    let message = panic_message(payload);
+                    self.send(AudioEvent::CallbackPanic { message });
+                }
+                self.panicking = true;
+            }
+        }
+    }
+
+    /// Fades the engines out, runs `action` from the audio thread, and fades them back in. If
+    /// another action is still waiting for the fade, it is replaced.
+    fn fade_through(&mut self, action: impl FnOnce() + Send + 'static) {
+        self.pending = Some(Box::new(action));
+    }
+
+    /// Runs the action waiting for a fade straight away, for when the device isn't playing.
+    pub(crate) fn finish_fade(&mut self) {
+        if let Some(action) = self.pending.take() {
+            action();
+        }
+        self.fade = 1.0;
+    }
+
+    /// Ramps the interleaved engine output in `mix` towards silence while an action is pending,
+    /// or back to full level otherwise, over one block.
+    fn apply_fade(&mut self) {
+        let target = if self.pending.is_some() { 0.0 } else { 1.0 };
+        if self.fade == target && target == 1.0 {
+            return;
+        }
+        let step = 1.0 / SoundEngine::render_buffer_len() as f32;
+        let channels = self.layout.channels() as usize;
+        for frame in self.mix.chunks_exact_mut(channels) {
+            self.fade = if target > self.fade {
+                (self.fade + step).min(target)
+            } else if self.fade > step {
+                self.fade - step
+            } else {
+                target
+            };
+            for sample in frame {
+                *sample *= self.fade;
+            }
+        }
+    }
+
+    /// Renders a block of every engine and mixes them into `block`, ducking them if necessary.
+    /// `followed` is whether the level of the trigger of the ducking has already been followed
+    /// for this block.
+    fn mix_engines(&mut self, block: &mut [f32], mut followed: bool) {
+        let channels = self.layout.channels() as usize;
+        let scratch = &mut self.scratch;
+        let trigger = self
+            .ducker
+            .as_ref()
+            .and_then(|ducker| ducker.ducking.trigger_engine())
+            .and_then(|id| self.engines.iter().position(|a| a.id == id));
+        if let (Some(index), Some(ducker)) = (trigger, &mut self.ducker) {
+            self.engines[index].mix_into(scratch, block, &self.default_routing, None);
+            // Nothing else has been mixed yet, so the block holds just the trigger.
+            ducker.follow(
+                block
+                    .chunks_exact(channels)
+                    .map(|frame| frame.iter().fold(0.0, |peak, s| s.abs().max(peak))),
+            );
+            followed = true;
+        }
+        if let (false, Some(ducker)) = (followed, &mut self.ducker) {
+            ducker.follow(iter::repeat_n(0.0, block.len() / channels));
+        }
+
+        for (index, attached) in self.engines.iter_mut().enumerate() {
+            if Some(index) == trigger {
+                continue;
+            }
+            let ducking = self
+                .ducker
+                .as_ref()
+                .filter(|ducker| ducker.ducks(attached.id))
+                .map(Ducker::gains);
+            attached.mix_into(scratch, block, &self.default_routing, ducking);
+        }
+    }
+
+    /// Renders and mixes the next block of output into `self.mix`.
+    fn mix_block(&mut self) {
+        if self.fade == 0.0 {
+            if let Some(action) = self.pending.take() {
+                action();
+            }
+        }
+
+        let block_len = SoundEngine::render_buffer_len();
+        let channels = self.layout.channels() as usize;
+        let mix = &mut self.mix;
+        mix.clear();
+        mix.resize(block_len * channels, 0.0);
+        let scratch = &mut self.scratch;
+        scratch.resize(block_len, (0.0, 0.0));
+
+        // Whatever triggers ducking is mixed first, so that the engines it ducks can follow its
+        // level.
+        let mut followed = false;
+        let ramp_frames = ramp::frames(self.gain_ramp);
+        let inputs = &mut self.input_mix;
+        inputs.clear();
+        if !self.inputs.is_empty() {
+            inputs.resize(block_len, (0.0, 0.0));
+            let trigger = self
+                .ducker
+                .as_ref()
+                .and_then(|ducker| ducker.ducking.trigger_input())
+                .filter(|trigger| self.inputs.iter().any(|i| i.ptr_eq(trigger)))
+                .cloned();
+            if let (Some(trigger), Some(ducker)) = (&trigger, &mut self.ducker) {
+                trigger.mix_into(inputs, self.underrun_fill, ramp_frames);
+                ducker.follow(inputs.iter().map(|f| f.0.abs().max(f.1.abs())));
+                followed = true;
+            }
+            for input in &self.inputs {
+                if !trigger.as_ref().is_some_and(|t| t.ptr_eq(input)) {
+                    input.mix_into(inputs, self.underrun_fill, ramp_frames);
+                }
+            }
+        }
+
+        // The engines are resampled if they aren't playing at the normal rate. If that panics,
+        // the buffered audio is lost, but the rate is kept.
+        let mut mix = mem::take(&mut self.mix);
+        let rate = self.playback_rate;
+        if rate == 1.0 && self.varispeed.is_empty() {
+            self.mix_engines(&mut mix, followed);
+        } else {
+            let mut varispeed = mem::take(&mut self.varispeed);
+            varispeed.process(&mut mix, channels, rate, |source| {
+                let start = source.len();
+                source.resize(start + block_len * channels, 0.0);
+                self.mix_engines(&mut source[start..], followed);
+            });
+            self.varispeed = varispeed;
+        }
+        self.mix = mix;
+        self.apply_fade();
+        if self.ducker.as_ref().is_some_and(Ducker::is_retired) {
+            self.ducker = None;
+        }
+
+        let (mix, scratch) = (&mut self.mix, &mut self.scratch);
+        if !self.input_mix.is_empty() {
+            self.default_routing.mix_into(&self.input_mix, mix, 1.0);
+        }
+        if let Some(injector) = &mut self.injector {
+            injector.process(mix, self.layout.channels() as usize, self.injection_mode);
+        }
+        if let Some(high_pass) = &mut self.high_pass {
+            high_pass.process(mix, self.layout.channels() as usize);
+        }
+        if let Some(loudness) = &mut self.loudness {
+            loudness.process(mix, self.layout);
+        }
+        for rumble in &mut self.rumbles {
+            rumble.measure(mix, self.layout.channels() as usize);
+        }
+        self.master.apply(mix, self.layout.channels() as usize);
+        if !self.loopbacks.is_empty() || !self.network_sinks.is_empty() {
+            self.downmix.fold_into(mix, scratch);
+            for loopback in &self.loopbacks {
+                loopback.write(scratch);
+            }
+            for sink in &self.network_sinks {
+                sink.write(scratch);
+            }
+        }
+        self.image.apply(mix, self.layout, ramp_frames);
+        if let Some(tone) = &mut self.test_tone {
+            tone.play(mix, self.layout.channels() as usize);
+            if tone.is_finished() {
+                self.test_tone = None;
+            }
+        }
+    }
+
+    /// Fills `out` with what `render` renders at the engine's rate, resampled to the device's
+    /// rate if that differs.
+    fn resample(&mut self, out: &mut [f32], render: fn(&mut Self, &mut [f32])) {
+        if self.rate_correction == 1.0 && self.resampler.is_empty() {
+            return render(self, out);
+        }
+        let channels = self.layout.channels() as usize;
+        let block_len = SoundEngine::render_buffer_len() * channels;
+        let mut resampler = mem::take(&mut self.resampler);
+        resampler.process(out, channels, self.rate_correction, |source| {
+            let start = source.len();
+            source.resize(start + block_len, 0.0);
+            render(self, &mut source[start..]);
+        });
+        self.resampler = resampler;
+    }
+
+    /// Fills `out` with the output, mixing as many blocks as it takes.
+    fn render(&mut self, out: &mut [f32]) {
+        let channels = self.layout.channels() as usize;
+        // The engine always renders whole blocks, but the device's buffer may be a different
+        // size, so blocks are split across, or combined into, as many buffers as necessary.
+        let mut buf = &mut *out;
+        while !buf.is_empty() {
+            if self.mix_pos == self.mix.len() {
+                self.mix_block_or_fill();
+                self.mix_pos = 0;
+                if let Some((handoff, _)) = &self.handoff_in {
+                    handoff.push(&self.mix);
+                }
+            }
+            let len = buf.len().min(self.mix.len() - self.mix_pos);
+            let (head, tail) = buf.split_at_mut(len);
+            head.copy_from_slice(&self.mix[self.mix_pos..self.mix_pos + len]);
+            self.mix_pos += len;
+            buf = tail;
+        }
+        if let Some((_, fade)) = &mut self.handoff_in {
+            fade.apply_interleaved(out, channels, Crossfade::level_in);
+            if fade.is_finished() {
+                self.handoff_in = None;
+            }
+        }
+        self.apply_trim(out);
+    }
+
+    /// Fills `out` with what the device that took over from this one hands it, fading it out.
+    fn render_handoff(&mut self, out: &mut [f32]) {
+        let channels = self.layout.channels() as usize;
+        let Some((handoff, fade)) = &mut self.handoff_out else {
+            return;
+        };
+        let len = handoff.pop(out);
+        self.filler
+            .record(self.underrun_fill, out[..len].iter().copied(), channels);
+        self.filler
+            .fill(self.underrun_fill, &mut out[len..], channels);
+        fade.apply_interleaved(out, channels, Crossfade::level_out);
+        self.apply_trim(out);
+    }
+}
+
+impl<R: BlockRenderer> AudioCallback for Callback<R> {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [Self::Channel]) {
+        if self.handoff_out.is_some() {
+            // Another device has taken over, so this one only fades out what it's handed.
+            self.resample(out, Self::render_handoff);
+            return;
+        }
+        self.heartbeat.fetch_add(1, Ordering::Relaxed);
+        if self.elevate {
+            self.elevate = false;
+            self.realtime = priority::elevate_current_thread();
+        }
+        let start = Instant::now();
+        let frames = out.len() / self.layout.channels() as usize;
+        let duration = Duration::from_secs_f64(
+            frames as f64 * f64::from(self.rate_correction) / SAMPLE_RATE as f64,
+        );
+        self.check_underrun(start, duration);
+        self.resample(out, Self::render);
+        telemetry::callback_served(start.elapsed().as_secs_f64() / duration.as_secs_f64());
+    }
+}
+
+/// Returns the message a panic was raised with.
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Unknown panic".into(),
+        },
+    }
+}
@@ -0,0 +1,291 @@
+//! A C API, for games written in other languages which already use SDL.
+//!
+//! Build the library with `cargo rustc --release --features capi --crate-type cdylib` (or
+//! `staticlib`), and include `include/rg3d_sound_sdl.h`, which is generated from this module by
+//! `cbindgen`.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{c_char, CStr, CString},
+    fs::File,
+    io::BufReader,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    ptr,
+};
+
+use rg3d_sound::{
+    algebra::Vector3,
+    buffer::{DataSource, SoundBufferResource},
+    context::SoundContext,
+    pool::Handle,
+    source::{generic::GenericSourceBuilder, spatial::SpatialSourceBuilder, SoundSource, Status},
+};
+use sdl2::Sdl;
+
+use crate::{DeviceSelector, OpenOptions, ReconnectPolicy, SdlSound};
+
+thread_local! {
+    /// Keeps SDL initialised for the life of the thread. The Rust bindings quit SDL once their
+    /// last handle is dropped, which would pull SDL out from under the game.
+    static SDL: RefCell<Option<Sdl>> = const { RefCell::new(None) };
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// A playback device, driving a single sound context.
+///
+/// SDL's audio handles belong to the thread which opened them, so every function taking an
+/// `Rg3dSound` must be called on the thread which called `rg3d_sound_open`, after the game
+/// initialised SDL.
+pub struct Rg3dSound {
+    sound: SdlSound,
+    context: SoundContext,
+    /// The sounds loaded so far, by path, so that playing a sound again doesn't reload it.
+    buffers: HashMap<PathBuf, SoundBufferResource>,
+}
+
+impl Rg3dSound {
+    fn buffer(&mut self, path: *const c_char) -> Result<SoundBufferResource, String> {
+        let path = PathBuf::from(string(path)?);
+        if let Some(buffer) = self.buffers.get(&path) {
+            return Ok(buffer.clone());
+        }
+        let data = BufReader::new(File::open(&path).map_err(|e| e.to_string())?);
+        let buffer = SoundBufferResource::new_generic(DataSource::File {
+            path: path.clone(),
+            data,
+        })
+        .map_err(|_| format!("Unsupported sound file: {}", path.display()))?;
+        self.buffers.insert(path, buffer.clone());
+        Ok(buffer)
+    }
+
+    fn play(
+        &mut self,
+        path: *const c_char,
+        looping: bool,
+        position: Option<Vector3<f32>>,
+    ) -> Result<u64, String> {
+        let builder = GenericSourceBuilder::new()
+            .with_buffer(self.buffer(path)?)
+            .with_looping(looping)
+            .with_play_once(!looping)
+            .with_status(Status::Playing);
+        let source = match position {
+            Some(position) => {
+                SpatialSourceBuilder::new(builder.build().map_err(|e| e.to_string())?)
+                    .with_position(position)
+                    .build_source()
+            }
+            None => builder.build_source().map_err(|e| e.to_string())?,
+        };
+        let handle = self.context.state().add_source(source);
+        Ok(u64::from(handle.index()) << 32 | u64::from(handle.generation()))
+    }
+}
+
+/// Runs `f`, returning `default` and recording the error for `rg3d_sound_last_error` if it
+/// fails or panics.
+fn guard<T>(default: T, f: impl FnOnce() -> Result<T, String>) -> T {
+    let error = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return value,
+        Ok(Err(error)) => error,
+        Err(payload) => crate::callback::panic_message(payload),
+    };
+    let error = CString::new(error.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = error);
+    default
+}
+
+fn string(s: *const c_char) -> Result<String, String> {
+    if s.is_null() {
+        return Err("Unexpected null string".into());
+    }
+    // Safety: the caller passed a valid nul-terminated string.
+    let s = unsafe { CStr::from_ptr(s) };
+    s.to_str()
+        .map(str::to_owned)
+        .map_err(|_| "String is not valid UTF-8".into())
+}
+
+/// Reads a 3D vector from an array of three floats.
+///
+/// # Safety
+/// `v` must point to three floats.
+unsafe fn vector(v: *const f32) -> Vector3<f32> {
+    let v = std::slice::from_raw_parts(v, 3);
+    Vector3::new(v[0], v[1], v[2])
+}
+
+/// Opens a playback device, by name, or the default device if `device` is null, and starts it
+/// playing a new sound context. Returns null on error.
+///
+/// # Safety
+/// `device` must be null or a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rg3d_sound_open(device: *const c_char) -> *mut Rg3dSound {
+    guard(ptr::null_mut(), || {
+        let device = match device.is_null() {
+            true => DeviceSelector::Default,
+            false => DeviceSelector::Name(string(device)?),
+        };
+        let sdl = SDL.with(|sdl| match &mut *sdl.borrow_mut() {
+            Some(sdl) => Ok::<_, String>(sdl.clone()),
+            slot => Ok(slot.insert(sdl2::init()?).clone()),
+        })?;
+        let sound = OpenOptions::new()
+            .device(device)
+            .reconnect(ReconnectPolicy::default())
+            .start_paused(false)
+            .open(&sdl.audio()?)?;
+        let context = SoundContext::new();
+        sound.engine().lock().unwrap().add_context(context.clone());
+        Ok(Box::into_raw(Box::new(Rg3dSound {
+            sound,
+            context,
+            buffers: HashMap::new(),
+        })))
+    })
+}
+
+/// Closes a device opened with `rg3d_sound_open`, stopping everything it was playing.
+///
+/// # Safety
+/// `sound` must be null or a device from `rg3d_sound_open` which hasn't been closed.
+#[no_mangle]
+pub unsafe extern "C" fn rg3d_sound_close(sound: *mut Rg3dSound) {
+    if !sound.is_null() {
+        guard((), || {
+            drop(Box::from_raw(sound));
+            Ok(())
+        });
+    }
+}
+
+/// Checks on the device, reopening it if it has been lost. Call this once per frame.
+///
+/// # Safety
+/// `sound` must be a device from `rg3d_sound_open` which hasn't been closed.
+#[no_mangle]
+pub unsafe extern "C" fn rg3d_sound_update(sound: *mut Rg3dSound) {
+    let sound = &mut *sound;
+    guard((), || {
+        sound.sound.update();
+        Ok(())
+    });
+}
+
+/// Plays a sound file (WAV or Ogg Vorbis) without positioning it, for music and UI sounds.
+/// Sounds which don't loop are removed once they finish. Returns an ID for `rg3d_sound_stop`,
+/// or 0 on error.
+///
+/// # Safety
+/// `sound` must be a device from `rg3d_sound_open` which hasn't been closed, and `path` a valid
+/// nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rg3d_sound_play(
+    sound: *mut Rg3dSound,
+    path: *const c_char,
+    looping: bool,
+) -> u64 {
+    let sound = &mut *sound;
+    guard(0, || sound.play(path, looping, None))
+}
+
+/// Plays a sound file at a position in the world, heard relative to the listener, and through
+/// HRTF after `rg3d_sound_load_hrtf`. Otherwise like `rg3d_sound_play`.
+///
+/// # Safety
+/// `sound` must be a device from `rg3d_sound_open` which hasn't been closed, `path` a valid
+/// nul-terminated string, and `position` must point to three floats.
+#[no_mangle]
+pub unsafe extern "C" fn rg3d_sound_play_at(
+    sound: *mut Rg3dSound,
+    path: *const c_char,
+    looping: bool,
+    position: *const f32,
+) -> u64 {
+    let sound = &mut *sound;
+    let position = vector(position);
+    guard(0, || sound.play(path, looping, Some(position)))
+}
+
+/// Stops and removes a sound played with `rg3d_sound_play` or `rg3d_sound_play_at`. Returns
+/// false if the sound has already finished.
+///
+/// # Safety
+/// `sound` must be a device from `rg3d_sound_open` which hasn't been closed.
+#[no_mangle]
+pub unsafe extern "C" fn rg3d_sound_stop(sound: *mut Rg3dSound, id: u64) -> bool {
+    let sound = &mut *sound;
+    guard(false, || {
+        let handle = Handle::<SoundSource>::new((id >> 32) as u32, id as u32);
+        let mut state = sound.context.state();
+        if id == 0 || !state.is_valid_handle(handle) {
+            return Err("No such sound".into());
+        }
+        state.sources_mut().free(handle);
+        Ok(true)
+    })
+}
+
+/// Moves the listener, facing along `look` with `up` pointing up, in a right-handed coordinate
+/// system.
+///
+/// # Safety
+/// `sound` must be a device from `rg3d_sound_open` which hasn't been closed, and `position`,
+/// `look` and `up` must each point to three floats.
+#[no_mangle]
+pub unsafe extern "C" fn rg3d_sound_set_listener(
+    sound: *mut Rg3dSound,
+    position: *const f32,
+    look: *const f32,
+    up: *const f32,
+) {
+    let sound = &mut *sound;
+    let (position, look, up) = (vector(position), vector(look), vector(up));
+    guard((), || {
+        let mut state = sound.context.state();
+        let listener = state.listener_mut();
+        listener.set_position(position);
+        listener.set_orientation_rh(look, up);
+        Ok(())
+    });
+}
+
+/// Sets the volume of everything played, as a linear gain where 1 is unchanged. The volume
+/// ramps to the new value, so it can be set every frame from a slider.
+///
+/// # Safety
+/// `sound` must be a device from `rg3d_sound_open` which hasn't been closed.
+#[no_mangle]
+pub unsafe extern "C" fn rg3d_sound_set_master_volume(sound: *mut Rg3dSound, volume: f32) {
+    let sound = &mut *sound;
+    guard((), || {
+        sound.sound.set_master_gain(volume);
+        Ok(())
+    });
+}
+
+/// Loads an HRIR sphere file and renders positioned sounds through HRTF with it, for players
+/// wearing headphones. Returns false on error.
+///
+/// # Safety
+/// `sound` must be a device from `rg3d_sound_open` which hasn't been closed, and `path` a valid
+/// nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rg3d_sound_load_hrtf(sound: *mut Rg3dSound, path: *const c_char) -> bool {
+    let sound = &mut *sound;
+    guard(false, || {
+        sound.sound.load_hrtf(string(path)?)?;
+        Ok(true)
+    })
+}
+
+/// Returns a description of the last error on this thread, or an empty string if there hasn't
+/// been one. The string is valid until the next error on this thread.
+#[no_mangle]
+pub extern "C" fn rg3d_sound_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
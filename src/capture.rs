@@ -0,0 +1,133 @@
+//! Capturing what the system plays, through the monitor sources some drivers expose.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use rg3d_sound::context::SAMPLE_RATE;
+use sdl2::{
+    audio::{AudioCallback, AudioDevice},
+    AudioSubsystem,
+};
+
+use crate::desired_spec;
+
+/// The prefix PulseAudio, and PipeWire through it, give the description of the monitor source of
+/// each output.
+const MONITOR_PREFIX: &str = "Monitor of ";
+
+/// The captured samples waiting to be read.
+type Ring = Arc<Mutex<VecDeque<f32>>>;
+
+/// Opens the monitor source of a playback device as a capture device, so that everything the
+/// system plays through it can be recorded, for example for a "broadcast what you hear" feature.
+///
+/// `output` is the name of the playback device, such as [`SdlSound::device_name`]. SDL has no way
+/// to tell which device is the default one, so `None`, as a device opened without a name has, is
+/// an error rather than a guess. Only drivers which expose monitors as capture devices, like
+/// PulseAudio, are supported. The capture starts paused. On error, returns the SDL error, or that
+/// no monitor of the device was found.
+///
+/// [`SdlSound::device_name`]: crate::SdlSound::device_name
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::DeviceSelector;
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let sound = rg3d_sound_sdl::OpenOptions::new()
+///     .device(DeviceSelector::Index(0))
+///     .open(&audio)
+///     .unwrap();
+///
+/// let capture = rg3d_sound_sdl::open_loopback_capture(&audio, sound.device_name()).unwrap();
+/// capture.resume();
+///
+/// // Later, on any thread:
+/// let mut samples = vec![0.0; 4096];
+/// let read = capture.read(&mut samples);
+/// ```
+pub fn open_loopback_capture(
+    subsystem: &AudioSubsystem,
+    output: Option<&str>,
+) -> Result<LoopbackCapture, String> {
+    let output = output.ok_or_else(|| {
+        "Can't find the monitor of the default device, as SDL doesn't name it".to_string()
+    })?;
+    let count = subsystem.num_audio_capture_devices().unwrap_or(0);
+    let name = (0..count)
+        .filter_map(|i| subsystem.audio_capture_device_name(i).ok())
+        .find(|name| name.strip_prefix(MONITOR_PREFIX) == Some(output))
+        .ok_or_else(|| format!("No monitor of {} found", output))?;
+
+    let ring = Ring::default();
+    let device = subsystem.open_capture(name.as_str(), &desired_spec(), |_| CaptureCallback {
+        ring: Arc::clone(&ring),
+    })?;
+    Ok(LoopbackCapture { device, name, ring })
+}
+
+/// A capture device recording a monitor source, opened with [`open_loopback_capture`].
+///
+/// Captured audio is interleaved stereo at [`SAMPLE_RATE`], and is kept for about a second; if it
+/// isn't read quickly enough, the oldest samples are dropped.
+pub struct LoopbackCapture {
+    device: AudioDevice<CaptureCallback>,
+    name: String,
+    ring: Ring,
+}
+
+impl LoopbackCapture {
+    /// Returns the name of the monitor source being captured.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Starts or continues capturing.
+    pub fn resume(&self) {
+        self.device.resume();
+    }
+
+    /// Stops capturing, keeping whatever has been captured but not read.
+    pub fn pause(&self) {
+        self.device.pause();
+    }
+
+    /// Returns the number of samples waiting to be read.
+    pub fn available(&self) -> usize {
+        self.ring.lock().unwrap().len()
+    }
+
+    /// Moves as many captured samples as are available into `buf`, oldest first, and returns the
+    /// number of samples read.
+    pub fn read(&self, buf: &mut [f32]) -> usize {
+        let mut ring = self.ring.lock().unwrap();
+        let len = buf.len().min(ring.len());
+        for (out, sample) in buf.iter_mut().zip(ring.drain(..len)) {
+            *out = sample;
+        }
+        len
+    }
+
+    /// Returns the SDL capture device.
+    pub fn device(&self) -> &AudioDevice<CaptureCallback> {
+        &self.device
+    }
+}
+
+/// The [`AudioCallback`] of a [`LoopbackCapture`], which buffers the captured samples.
+pub struct CaptureCallback {
+    ring: Ring,
+}
+
+impl AudioCallback for CaptureCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, buf: &mut [f32]) {
+        let mut ring = self.ring.lock().unwrap();
+        ring.extend(buf.iter());
+        let excess = ring.len().saturating_sub(SAMPLE_RATE as usize * 2);
+        ring.drain(..excess);
+    }
+}
@@ -0,0 +1,209 @@
+//! Persistable audio settings.
+
+use std::path::PathBuf;
+
+use rg3d_sound::hrtf::HrirSphere;
+
+use crate::{ChannelLayout, DeviceSelector, OpenOptions, SdlSound, SpecMismatchPolicy};
+
+/// The player's audio settings, in one place, so that they can be saved and restored. With the
+/// `serde` feature, this implements `Serialize` and `Deserialize`, and missing fields take their
+/// default values.
+///
+/// Open a device with these settings with [`SdlSound::from_config`], and change the settings of
+/// an open device with [`AudioConfig::apply`].
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::{AudioConfig, ChannelLayout, SdlSound};
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let mut config = AudioConfig::default();
+/// let mut sound = SdlSound::from_config(&audio, &config).unwrap();
+/// sound.resume();
+///
+/// // The player changed their settings.
+/// config.master_volume = 0.5;
+/// config.channel_layout = ChannelLayout::Surround51;
+/// config.apply(&mut sound).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct AudioConfig {
+    /// The device to play to.
+    pub device: DeviceSelector,
+    /// The master gain of the output, set with [`SdlSound::set_master_gain`].
+    pub master_volume: f32,
+    /// How the size of the device's buffer is negotiated.
+    pub latency_mode: SpecMismatchPolicy,
+    /// The channel layout to open the device with.
+    pub channel_layout: ChannelLayout,
+    /// Whether contexts are rendered with HRTF, for headphones.
+    pub hrtf: bool,
+    /// The HRIR sphere file to use for HRTF. If `None`, the sphere bundled with the
+    /// `bundled-hrir` feature is used.
+    pub hrir_sphere: Option<PathBuf>,
+    /// The SDL audio driver to use, or `None` for whichever is already in use.
+    pub driver: Option<String>,
+    /// Whether the output fades to silence while the game window doesn't have focus, as with
+    /// [`SdlSound::set_mute_on_focus_loss`].
+    pub mute_on_focus_loss: bool,
+}
+
+impl AudioConfig {
+    /// Returns options which open a device with these settings, leaving it paused.
+    pub fn open_options(&self) -> OpenOptions {
+        let mut options = OpenOptions::new();
+        self.update_options(&mut options);
+        options
+    }
+
+    /// Changes the settings of an open device to these. If the device, driver, channel layout or
+    /// latency mode changed, the device is reopened, keeping everything attached to its callback.
+    /// On error, returns a description of the setting which couldn't be applied. A device which
+    /// couldn't be reopened keeps playing as before. Switching drivers closes every open audio
+    /// device, so the device is closed before switching, and reopened on the old driver if the new
+    /// one fails, and the switch is refused while any other audio device is open.
+    pub fn apply(&self, sound: &mut SdlSound) -> Result<(), String> {
+        let mut options = sound.options().clone();
+        self.update_options(&mut options);
+        let driver_changed = options
+            .device
+            .required_driver(options.driver.as_deref())
+            .is_some();
+        let old = sound.options();
+        if options.device != old.device
+            || options.channel_layout != old.channel_layout
+            || options.spec_mismatch != old.spec_mismatch
+            || driver_changed
+        {
+            sound.reopen(options)?;
+        }
+        self.apply_to_engine(sound)
+    }
+
+    /// Applies the settings which don't need the device to be reopened.
+    pub(crate) fn apply_to_engine(&self, sound: &mut SdlSound) -> Result<(), String> {
+        sound.set_master_gain(self.master_volume);
+        sound.set_mute_on_focus_loss(self.mute_on_focus_loss);
+        if self.hrtf {
+            // The sphere is reloaded if it changed, or HRTF was enabled some other way.
+            if sound.config_hrir() != Some(&self.hrir_sphere) {
+                let sphere = self.load_hrir_sphere()?;
+                sound.set_config_hrtf(sphere, self.hrir_sphere.clone());
+            }
+        } else if sound.is_hrtf_enabled() {
+            sound.set_hrtf(None);
+        }
+        Ok(())
+    }
+
+    /// Returns the settings which differ between this config and `other`.
+    /// # Example
+    /// ```
+    /// use rg3d_sound_sdl::{AudioConfig, ConfigField};
+    ///
+    /// let old = AudioConfig::default();
+    /// let new = AudioConfig {
+    ///     master_volume: 0.5,
+    ///     hrtf: true,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(old.diff(&new), [ConfigField::MasterVolume, ConfigField::Hrtf]);
+    /// ```
+    pub fn diff(&self, other: &Self) -> Vec<ConfigField> {
+        let mut changed = Vec::new();
+        let mut check = |differs: bool, field| {
+            if differs {
+                changed.push(field);
+            }
+        };
+        check(self.device != other.device, ConfigField::Device);
+        check(
+            self.master_volume != other.master_volume,
+            ConfigField::MasterVolume,
+        );
+        check(
+            self.latency_mode != other.latency_mode,
+            ConfigField::LatencyMode,
+        );
+        check(
+            self.channel_layout != other.channel_layout,
+            ConfigField::ChannelLayout,
+        );
+        check(self.hrtf != other.hrtf, ConfigField::Hrtf);
+        check(
+            self.hrir_sphere != other.hrir_sphere,
+            ConfigField::HrirSphere,
+        );
+        check(self.driver != other.driver, ConfigField::Driver);
+        check(
+            self.mute_on_focus_loss != other.mute_on_focus_loss,
+            ConfigField::MuteOnFocusLoss,
+        );
+        changed
+    }
+
+    fn update_options(&self, options: &mut OpenOptions) {
+        options
+            .device(self.device.clone())
+            .spec_mismatch(self.latency_mode)
+            .channel_layout(self.channel_layout);
+        options.driver = self.driver.clone();
+    }
+
+    fn load_hrir_sphere(&self) -> Result<HrirSphere, String> {
+        match &self.hrir_sphere {
+            #[cfg(feature = "hrir")]
+            Some(path) => crate::load_hrir_sphere(path),
+            #[cfg(not(feature = "hrir"))]
+            Some(path) => HrirSphere::from_file(path, rg3d_sound::context::SAMPLE_RATE)
+                .map_err(|e| format!("Failed to load HRIR sphere: {:?}", e)),
+            #[cfg(feature = "bundled-hrir")]
+            None => Ok(crate::bundled_hrir_sphere()),
+            #[cfg(not(feature = "bundled-hrir"))]
+            None => Err("HRTF needs an HRIR sphere file".into()),
+        }
+    }
+}
+
+/// A setting in an [`AudioConfig`], as returned by [`AudioConfig::diff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ConfigField {
+    /// [`AudioConfig::device`].
+    Device,
+    /// [`AudioConfig::master_volume`].
+    MasterVolume,
+    /// [`AudioConfig::latency_mode`].
+    LatencyMode,
+    /// [`AudioConfig::channel_layout`].
+    ChannelLayout,
+    /// [`AudioConfig::hrtf`].
+    Hrtf,
+    /// [`AudioConfig::hrir_sphere`].
+    HrirSphere,
+    /// [`AudioConfig::driver`].
+    Driver,
+    /// [`AudioConfig::mute_on_focus_loss`].
+    MuteOnFocusLoss,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            device: DeviceSelector::Default,
+            master_volume: 1.0,
+            latency_mode: SpecMismatchPolicy::default(),
+            channel_layout: ChannelLayout::default(),
+            hrtf: false,
+            hrir_sphere: None,
+            driver: None,
+            mute_on_focus_loss: false,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Applying changes to a config file live, while the game runs.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{AudioConfig, AudioEvent, ConfigField, SdlSound};
+
+/// Parses the contents of a config file.
+type Parser = Box<dyn FnMut(&str) -> Result<AudioConfig, String>>;
+
+/// Watches a file of [`AudioConfig`] settings, and applies any changes made to it to a device
+/// live, for tuning audio settings without restarting the game.
+///
+/// The file is checked for changes from [`ConfigWatcher::update`], at most every half a second by
+/// default. Once a change has been applied, [`AudioEvent::ConfigReloaded`] is sent with the
+/// settings which changed, along with any events from reopening the device. If the file can't
+/// be read, parsed or applied, [`AudioEvent::ConfigReloadFailed`] is sent instead, and the file
+/// is checked again once it next changes.
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::{ConfigWatcher, SdlSound};
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let mut watcher = ConfigWatcher::new("audio.toml").unwrap();
+/// let mut sound = SdlSound::from_config(&audio, watcher.config()).unwrap();
+/// sound.resume();
+///
+/// // In the game loop:
+/// watcher.update(&mut sound);
+/// sound.update();
+/// ```
+pub struct ConfigWatcher {
+    path: PathBuf,
+    parse: Parser,
+    /// The settings last read from the file and applied.
+    config: AudioConfig,
+    /// When the file was last modified, as of the last check.
+    modified: Option<SystemTime>,
+    interval: Duration,
+    next_check: Instant,
+}
+
+impl ConfigWatcher {
+    /// Reads the TOML config file at `path`, to watch for changes to it. On error, returns a
+    /// description of why the file couldn't be read or parsed.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self, String> {
+        Self::with_parser(path, |s| toml::from_str(s).map_err(|e| e.to_string()))
+    }
+
+    /// Like [`ConfigWatcher::new`], but reads the file with `parse`, for config files in other
+    /// formats.
+    pub fn with_parser(
+        path: impl Into<PathBuf>,
+        parse: impl FnMut(&str) -> Result<AudioConfig, String> + 'static,
+    ) -> Result<Self, String> {
+        let mut watcher = Self {
+            path: path.into(),
+            parse: Box::new(parse),
+            config: AudioConfig::default(),
+            modified: None,
+            interval: Duration::from_millis(500),
+            next_check: Instant::now(),
+        };
+        watcher.modified = watcher.modified();
+        watcher.config = watcher.read()?;
+        Ok(watcher)
+    }
+
+    /// Returns the path to the file being watched.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the settings last read from the file.
+    pub fn config(&self) -> &AudioConfig {
+        &self.config
+    }
+
+    /// Sets how often the file is checked for changes. Defaults to every half a second.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Checks whether the file has changed, if it's due to be checked, and if so applies the
+    /// changed settings to `sound`. Returns the settings which changed.
+    pub fn update(&mut self, sound: &mut SdlSound) -> Vec<ConfigField> {
+        let now = Instant::now();
+        if now < self.next_check {
+            return Vec::new();
+        }
+        self.next_check = now + self.interval;
+        let modified = self.modified();
+        if modified == self.modified {
+            return Vec::new();
+        }
+        self.modified = modified;
+
+        let config = match self.read() {
+            Ok(config) => config,
+            Err(error) => {
+                sound.send(AudioEvent::ConfigReloadFailed { error });
+                return Vec::new();
+            }
+        };
+        let changed = self.config.diff(&config);
+        if changed.is_empty() {
+            return changed;
+        }
+        if let Err(error) = config.apply(sound) {
+            sound.send(AudioEvent::ConfigReloadFailed { error });
+            return Vec::new();
+        }
+        self.config = config;
+        sound.send(AudioEvent::ConfigReloaded {
+            changed: changed.clone(),
+        });
+        changed
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    fn read(&mut self) -> Result<AudioConfig, String> {
+        let contents = fs::read_to_string(&self.path)
+            .map_err(|e| format!("Failed to read {}: {}", self.path.display(), e))?;
+        (self.parse)(&contents)
+    }
+}
@@ -0,0 +1,103 @@
+//! Crossfading from one source of audio to another.
+
+use std::{
+    collections::VecDeque,
+    f32::consts::FRAC_PI_2,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rg3d_sound::context::SAMPLE_RATE;
+
+/// The progress of an equal-power crossfade.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Crossfade {
+    pos: usize,
+    len: usize,
+    /// The level the outgoing audio fades out from, in case it was itself still fading in.
+    from: f32,
+}
+
+impl Crossfade {
+    pub(crate) fn new(duration: Duration, from: f32) -> Self {
+        Self {
+            pos: 0,
+            len: (duration.as_secs_f64() * SAMPLE_RATE as f64) as usize,
+            from,
+        }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.pos >= self.len
+    }
+
+    /// Returns the level of the incoming audio.
+    pub(crate) fn level_in(&self) -> f32 {
+        if self.is_finished() {
+            return 1.0;
+        }
+        (self.pos as f32 / self.len as f32 * FRAC_PI_2).sin()
+    }
+
+    /// Returns the level of the outgoing audio.
+    pub(crate) fn level_out(&self) -> f32 {
+        if self.is_finished() {
+            return 0.0;
+        }
+        self.from * (self.pos as f32 / self.len as f32 * FRAC_PI_2).cos()
+    }
+
+    /// Scales a block of frames by either level, advancing the crossfade.
+    pub(crate) fn apply(&mut self, frames: &mut [(f32, f32)], level: fn(&Self) -> f32) {
+        for frame in frames {
+            let level = level(self);
+            frame.0 *= level;
+            frame.1 *= level;
+            self.pos += 1;
+        }
+    }
+
+    /// Scales a block of interleaved frames by either level, advancing the crossfade.
+    pub(crate) fn apply_interleaved(
+        &mut self,
+        frames: &mut [f32],
+        channels: usize,
+        level: fn(&Self) -> f32,
+    ) {
+        for frame in frames.chunks_exact_mut(channels) {
+            let level = level(self);
+            for sample in frame {
+                *sample *= level;
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+/// A short ring of interleaved output, through which the callback playing to a new device hands
+/// a copy of its output to the device it replaced, so that the old device can fade out.
+#[derive(Clone, Default)]
+pub(crate) struct Handoff {
+    ring: Arc<Mutex<VecDeque<f32>>>,
+}
+
+impl Handoff {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn push(&self, samples: &[f32]) {
+        self.ring.lock().unwrap().extend(samples);
+    }
+
+    /// Fills `out` with as many of the oldest samples in the ring as there are, returning how
+    /// many.
+    pub(crate) fn pop(&self, out: &mut [f32]) -> usize {
+        let mut ring = self.ring.lock().unwrap();
+        let len = out.len().min(ring.len());
+        for (out, sample) in out.iter_mut().zip(ring.drain(..len)) {
+            *out = sample;
+        }
+        len
+    }
+}
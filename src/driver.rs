@@ -0,0 +1,174 @@
+//! Switching SDL's audio driver.
+//!
+//! Switching drivers reinitializes SDL's audio subsystem, which closes every open audio device
+//! and frees their IDs to be given to the next devices opened. The handle of a device closed this
+//! way must never be dropped once another device might have its ID, as dropping it would close
+//! that device instead, so drivers are only switched while no devices are open, other than ones
+//! the caller is about to replace and leak with [`Device::mark_closed`].
+
+use std::{
+    ffi::{CStr, CString},
+    mem::{self, ManuallyDrop},
+    ops::{Deref, DerefMut},
+    sync::{Mutex, PoisonError},
+};
+
+use sdl2::{audio::AudioDevice, sys};
+
+use crate::Callback;
+
+/// The number of audio devices SDL can have open at once, whose IDs run from 1 to this.
+const DEVICE_SLOTS: u32 = 16;
+
+/// The driver in use before the last switch to the dummy or disk driver, which opening a real
+/// device switches back to.
+static REAL_DRIVER: Mutex<Option<String>> = Mutex::new(None);
+
+/// Returns the driver in use, or `None` if there isn't one, as switching to a driver which fails
+/// to initialize leaves none.
+pub(crate) fn current() -> Option<&'static str> {
+    // SAFETY: SDL returns either null or the name of the driver, which is static.
+    unsafe {
+        let name = sys::SDL_GetCurrentAudioDriver();
+        (!name.is_null()).then(|| CStr::from_ptr(name).to_str().unwrap_or_default())
+    }
+}
+
+/// Returns whether a driver doesn't play to real devices, so is only switched to for
+/// [`DeviceSelector::Dummy`][crate::DeviceSelector::Dummy] or
+/// [`DeviceSelector::Disk`][crate::DeviceSelector::Disk].
+pub(crate) fn is_virtual(driver: &str) -> bool {
+    matches!(driver, "dummy" | "disk")
+}
+
+/// Returns the driver in use before the last switch to a virtual driver, if any.
+pub(crate) fn real_driver() -> Option<String> {
+    REAL_DRIVER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Returns the number of open audio devices, of any kind and whoever opened them, including
+/// lost devices, which SDL keeps open until they are closed.
+pub(crate) fn open_devices() -> usize {
+    (1..=DEVICE_SLOTS)
+        .filter(|&id| {
+            // SAFETY: These functions can be called for any ID. SDL only sets an error for IDs
+            // which aren't open, which is what tells a closed device from a lost one, as both are
+            // stopped.
+            unsafe {
+                sys::SDL_ClearError();
+                let status = sys::SDL_GetAudioDeviceStatus(id);
+                status != sys::SDL_AudioStatus::SDL_AUDIO_STOPPED
+                    || CStr::from_ptr(sys::SDL_GetError()).to_bytes().is_empty()
+            }
+        })
+        .count()
+}
+
+/// Checks that no more than the `owned` audio devices the caller has are open, so that switching
+/// to `driver` wouldn't close anyone else's. On error, returns a description of why not.
+pub(crate) fn check_can_switch(driver: &str, owned: usize) -> Result<(), String> {
+    if open_devices() > owned {
+        return Err(format!(
+            "Can't switch to the {} audio driver while other audio devices are open",
+            driver
+        ));
+    }
+    Ok(())
+}
+
+/// Reinitializes SDL's audio subsystem, which must be initialized, with the named driver, unless
+/// it is already in use. This closes every open audio device, so it fails if more than `owned`
+/// devices are open, where `owned` is the number of devices the caller has and will leak after
+/// switching. On error, returns a description of why the driver can't be switched, or the SDL
+/// error, in which case the devices have been closed all the same.
+pub(crate) fn init_driver(driver: &str, owned: usize) -> Result<(), String> {
+    let current = current();
+    if current == Some(driver) {
+        return Ok(());
+    }
+    check_can_switch(driver, owned)?;
+    let name = CString::new(driver).map_err(|e| e.to_string())?;
+    // SAFETY: The audio subsystem is initialized, and the driver name is a valid C string which
+    // outlives the call.
+    match unsafe { sys::SDL_AudioInit(name.as_ptr()) } {
+        0 => {
+            if let Some(current) = current.filter(|&c| is_virtual(driver) && !is_virtual(c)) {
+                *REAL_DRIVER.lock().unwrap_or_else(PoisonError::into_inner) = Some(current.into());
+            }
+            Ok(())
+        }
+        _ => Err(sdl2::get_error()),
+    }
+}
+
+/// A playback device of an [`SdlSound`][crate::SdlSound], which is leaked rather than closed
+/// when dropped once SDL has closed it by switching drivers.
+pub(crate) struct Device {
+    device: ManuallyDrop<AudioDevice<Callback>>,
+    closed: bool,
+}
+
+impl Device {
+    pub(crate) fn new(device: AudioDevice<Callback>) -> Self {
+        Self {
+            device: ManuallyDrop::new(device),
+            closed: false,
+        }
+    }
+
+    /// Marks the device as closed by SDL switching drivers, so that it's leaked when dropped.
+    pub(crate) fn mark_closed(&mut self) {
+        self.closed = true;
+    }
+
+    /// Takes the callback off a closed device, leaving a placeholder to be leaked. A closed device
+    /// can only be locked until another device is opened, as SDL ignores IDs which aren't open.
+    pub(crate) fn take_callback(&mut self) -> Callback {
+        debug_assert!(self.closed);
+        mem::replace(&mut *self.device.lock(), Callback::new(crate::new_engine()))
+    }
+
+    /// Puts a callback taken with [`Device::take_callback`] back, before another device has been
+    /// opened.
+    pub(crate) fn put_callback(&mut self, callback: Callback) {
+        *self.device.lock() = callback;
+    }
+
+    /// Returns whether SDL has closed the device by switching drivers.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the SDL device, which is still closed if SDL closed it.
+    pub(crate) fn into_inner(self) -> AudioDevice<Callback> {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used again, or dropped.
+        unsafe { ManuallyDrop::take(&mut this.device) }
+    }
+}
+
+impl Deref for Device {
+    type Target = AudioDevice<Callback>;
+
+    fn deref(&self) -> &AudioDevice<Callback> {
+        &self.device
+    }
+}
+
+impl DerefMut for Device {
+    fn deref_mut(&mut self) -> &mut AudioDevice<Callback> {
+        &mut self.device
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        if !self.closed {
+            // SAFETY: The device is never used again.
+            unsafe { ManuallyDrop::drop(&mut self.device) }
+        }
+    }
+}
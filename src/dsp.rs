@@ -0,0 +1,86 @@
+//! Filters used by the processing stages of a [`Callback`][crate::Callback].
+
+/// A biquad filter in direct form I.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    /// Creates a filter from its coefficients, normalized so that `a0` is 1.
+    pub(crate) fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            ..Self::default()
+        }
+    }
+
+    /// Creates a second-order Butterworth low-pass filter with the given cutoff frequency, in Hz.
+    pub(crate) fn low_pass(cutoff: f32) -> Self {
+        let w0 =
+            2.0 * std::f64::consts::PI * cutoff as f64 / rg3d_sound::context::SAMPLE_RATE as f64;
+        let alpha = w0.sin() * std::f64::consts::FRAC_1_SQRT_2;
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        let b1 = (1.0 - cos) / a0;
+        Self::new(
+            [b1 / 2.0, b1, b1 / 2.0],
+            [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        )
+    }
+
+    /// Filters a single sample.
+    pub(crate) fn process(&mut self, x: f32) -> f32 {
+        let x = x as f64;
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y as f32
+    }
+}
+
+/// A one-pole high-pass filter for each channel of interleaved frames.
+#[derive(Debug, Clone)]
+pub(crate) struct HighPass {
+    cutoff: f32,
+    coefficient: f32,
+    /// The previous input and output of each channel.
+    state: Vec<(f32, f32)>,
+}
+
+impl HighPass {
+    /// Creates a filter with the given cutoff frequency, in Hz, which must be above 0 and no more
+    /// than half the sample rate for the filter to be stable.
+    pub(crate) fn new(cutoff: f32) -> Self {
+        let rc = 1.0 / (2.0 * std::f32::consts::PI * cutoff);
+        let dt = 1.0 / rg3d_sound::context::SAMPLE_RATE as f32;
+        Self {
+            cutoff,
+            coefficient: rc / (rc + dt),
+            state: Vec::new(),
+        }
+    }
+
+    /// Returns the cutoff frequency, in Hz.
+    pub(crate) fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    /// Filters interleaved frames with the given number of channels in place.
+    pub(crate) fn process(&mut self, frames: &mut [f32], channels: usize) {
+        self.state.resize(channels, (0.0, 0.0));
+        for frame in frames.chunks_exact_mut(channels) {
+            for (sample, (x1, y1)) in frame.iter_mut().zip(&mut self.state) {
+                *y1 = self.coefficient * (*y1 + *sample - *x1);
+                *x1 = *sample;
+                *sample = *y1;
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Ducking some engines attached to a [`Callback`][crate::Callback] whenever another plays.
+
+use std::time::Duration;
+
+use rg3d_sound::context::SAMPLE_RATE;
+
+use crate::{EngineId, ExternalInput};
+
+/// How quickly the level of the trigger is followed, in seconds. This is independent of how
+/// quickly the ducked engines respond, and only smooths over the gaps between individual cycles.
+const DETECTOR_ATTACK: f64 = 0.005;
+const DETECTOR_RELEASE: f64 = 0.05;
+
+/// The audio whose level triggers [`Ducking`].
+#[derive(Clone)]
+pub enum Sidechain {
+    /// An attached engine, such as one made for a dialogue context with
+    /// [`Callback::attach_context`][crate::Callback::attach_context].
+    Engine(EngineId),
+    /// An external input added with [`Callback::add_input`][crate::Callback::add_input].
+    Input(ExternalInput),
+}
+
+/// Turns some engines attached to a [`Callback`][crate::Callback] down while a sidechain
+/// trigger is playing, for example so that dialogue ducks the music. Set it with
+/// [`Callback::set_ducking`][crate::Callback::set_ducking].
+///
+/// Whenever the peak level of the trigger rises above `threshold`, the gain of each of the
+/// `ducked` engines falls by `reduction` over `attack`, and once the trigger falls silent again it
+/// recovers over `release`. The trigger itself and other engines aren't affected.
+/// # Example
+/// ```no_run
+/// use rg3d_sound::context::SoundContext;
+/// use rg3d_sound_sdl::{Ducking, Sidechain};
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+///
+/// let mut callback = device.lock();
+/// let music = callback.attach_context(SoundContext::new(), 1.0);
+/// let dialogue = callback.attach_context(SoundContext::new(), 1.0);
+/// callback.set_ducking(Ducking {
+///     reduction: 9.0,
+///     ..Ducking::new(Sidechain::Engine(dialogue), vec![music])
+/// });
+/// ```
+#[derive(Clone)]
+pub struct Ducking {
+    /// The audio whose level triggers the ducking.
+    pub trigger: Sidechain,
+    /// The engines which are turned down.
+    pub ducked: Vec<EngineId>,
+    /// The peak level of the trigger above which ducking starts, in dBFS.
+    pub threshold: f32,
+    /// How far the ducked engines are turned down, in dB.
+    pub reduction: f32,
+    /// How long the ducked engines take to turn down.
+    pub attack: Duration,
+    /// How long the ducked engines take to recover.
+    pub release: Duration,
+}
+
+impl Ducking {
+    /// Creates ducking of the given engines by 12dB whenever the trigger rises above -40dBFS,
+    /// with a 50ms attack and 500ms release.
+    pub fn new(trigger: Sidechain, ducked: Vec<EngineId>) -> Self {
+        Self {
+            trigger,
+            ducked,
+            threshold: -40.0,
+            reduction: 12.0,
+            attack: Duration::from_millis(50),
+            release: Duration::from_millis(500),
+        }
+    }
+
+    /// Returns the id of the engine triggering the ducking, if the trigger is an engine.
+    pub(crate) fn trigger_engine(&self) -> Option<EngineId> {
+        match self.trigger {
+            Sidechain::Engine(id) => Some(id),
+            Sidechain::Input(_) => None,
+        }
+    }
+
+    /// Returns the input triggering the ducking, if the trigger is an input.
+    pub(crate) fn trigger_input(&self) -> Option<&ExternalInput> {
+        match &self.trigger {
+            Sidechain::Input(input) => Some(input),
+            Sidechain::Engine(_) => None,
+        }
+    }
+}
+
+/// The state of [`Ducking`] owned by the callback.
+pub(crate) struct Ducker {
+    pub(crate) ducking: Ducking,
+    /// The followed level of the trigger.
+    level: f32,
+    /// The gain currently applied to the ducked engines.
+    gain: f32,
+    /// The gain of each frame of the current block.
+    gains: Vec<f32>,
+    /// Whether the ducking has been turned off, and the ducked engines are recovering before the
+    /// ducker is dropped.
+    retiring: bool,
+}
+
+impl Ducker {
+    /// Creates a ducker starting from `gain`, so that replacing one ducker with another doesn't
+    /// make the ducked engines jump in level.
+    pub(crate) fn new(ducking: Ducking, gain: f32) -> Self {
+        Self {
+            ducking,
+            level: 0.0,
+            gain,
+            gains: Vec::new(),
+            retiring: false,
+        }
+    }
+
+    /// Returns the gain applied to the ducked engines.
+    pub(crate) fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Stops ducking, letting the ducked engines recover over the release time.
+    pub(crate) fn retire(&mut self) {
+        self.retiring = true;
+    }
+
+    /// Returns whether the ducking has been turned off.
+    pub(crate) fn is_retiring(&self) -> bool {
+        self.retiring
+    }
+
+    /// Returns whether the ducking has been turned off and the ducked engines have recovered, so
+    /// the ducker can be dropped.
+    pub(crate) fn is_retired(&self) -> bool {
+        self.retiring && self.gain >= 0.999
+    }
+
+    /// Returns whether an engine is ducked.
+    pub(crate) fn ducks(&self, id: EngineId) -> bool {
+        self.ducking.trigger_engine() != Some(id) && self.ducking.ducked.contains(&id)
+    }
+
+    /// Returns the gain of each frame of the current block.
+    pub(crate) fn gains(&self) -> &[f32] {
+        &self.gains
+    }
+
+    /// Follows the peak level of each frame of the trigger for the current block, working out the
+    /// gain of the ducked engines for each frame.
+    pub(crate) fn follow(&mut self, peaks: impl Iterator<Item = f32>) {
+        let detector_attack = coefficient(Duration::from_secs_f64(DETECTOR_ATTACK));
+        let detector_release = coefficient(Duration::from_secs_f64(DETECTOR_RELEASE));
+        let attack = coefficient(self.ducking.attack);
+        let release = coefficient(self.ducking.release);
+        let threshold = db_to_gain(self.ducking.threshold);
+        let ducked = db_to_gain(-self.ducking.reduction.abs());
+
+        self.gains.clear();
+        for peak in peaks {
+            let rate = if peak > self.level {
+                detector_attack
+            } else {
+                detector_release
+            };
+            self.level += (peak - self.level) * rate;
+            let (target, rate) = if self.level > threshold && !self.retiring {
+                (ducked, attack)
+            } else {
+                (1.0, release)
+            };
+            self.gain += (target - self.gain) * rate;
+            self.gains.push(self.gain);
+        }
+    }
+}
+
+/// Returns the per-frame smoothing coefficient which moves about two thirds of the way to a
+/// target over `time`.
+fn coefficient(time: Duration) -> f32 {
+    let frames = time.as_secs_f64() * SAMPLE_RATE as f64;
+    if frames < 1.0 {
+        1.0
+    } else {
+        (1.0 - (-1.0 / frames).exp()) as f32
+    }
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
@@ -0,0 +1,28 @@
+//! Error types.
+
+use std::{error::Error, fmt};
+
+use crate::DeviceSelector;
+
+/// The error returned when every device given to
+/// [`open_with_fallbacks`][crate::open_with_fallbacks] failed to open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackError {
+    /// Each device that was tried, in order, along with the error it failed with.
+    pub errors: Vec<(DeviceSelector, String)>,
+}
+
+impl fmt::Display for FallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.errors.is_empty() {
+            return write!(f, "No audio devices to try");
+        }
+        write!(f, "Failed to open any audio device")?;
+        for (device, error) in &self.errors {
+            write!(f, "; {:?}: {}", device, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for FallbackError {}
@@ -0,0 +1,95 @@
+//! Typed events describing the lifecycle of a playback device.
+
+use std::{
+    sync::mpsc::{self, Receiver, SyncSender},
+    time::Duration,
+};
+
+use sdl2::audio::AudioStatus;
+
+use crate::{ConfigField, ObtainedSpec};
+
+/// The number of events that can be waiting to be received before more are dropped.
+pub(crate) const CAPACITY: usize = 64;
+
+/// Something that happened to a playback device, received from [`SdlSound::events`][crate::SdlSound::events].
+///
+/// Events are sent from both the game's thread and the audio thread, but the audio thread never
+/// waits for the game to receive them. If too many events are left waiting, new ones are dropped.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum AudioEvent {
+    /// A device was opened. `device` is the name of the device, or `None` for the default device.
+    DeviceOpened {
+        device: Option<String>,
+        spec: ObtainedSpec,
+    },
+    /// The device started or stopped playing, whether because it was resumed or paused, the OS
+    /// suspended or resumed audio, or the device was lost, in which case `status` is
+    /// [`AudioStatus::Stopped`].
+    StatusChanged { status: AudioStatus },
+    /// The device was lost, for example because a USB headset was unplugged, and is no longer
+    /// playing anything.
+    DeviceLost { device: Option<String> },
+    /// Playback moved from one device to another.
+    DeviceSwitched {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// The device waited longer than expected for audio, so playback probably glitched. `gap` is
+    /// the time between the last two callbacks.
+    Underrun { gap: Duration },
+    /// Rendering panicked. The block being rendered was played as silence.
+    CallbackPanic { message: String },
+    /// The device is now playing with a different spec.
+    SpecChanged { spec: ObtainedSpec },
+    /// Attempt number `attempt`, counting from 1, to reopen a lost device will be made after
+    /// `retry_in`, either because the device was just lost or the previous attempt failed.
+    Reconnecting { attempt: u32, retry_in: Duration },
+    /// A lost device was reopened, and is playing again if it was before it was lost.
+    Reconnected { device: Option<String> },
+    /// Reopening a lost device was given up on after `attempts` attempts.
+    ReconnectFailed { attempts: u32 },
+    /// The device is playing, but the driver hasn't asked for audio for `silent_for`, so nothing
+    /// is being heard. Only sent with a [`WatchdogPolicy`][crate::WatchdogPolicy].
+    DeviceStalled {
+        device: Option<String>,
+        silent_for: Duration,
+    },
+    /// The driver started playing the device at `rate`, in Hz. Only sent with a
+    /// [`RateChangePolicy`][crate::RateChangePolicy], which says how the change is handled.
+    SampleRateChanged { device: Option<String>, rate: u32 },
+    /// The settings were changed live after a config file was edited, and `changed` lists the
+    /// settings which changed. Only sent by a `ConfigWatcher`, with the `config-watch` feature.
+    ConfigReloaded { changed: Vec<ConfigField> },
+    /// An edited config file couldn't be read, parsed or applied, so some or all of its settings
+    /// weren't applied. Only sent by a `ConfigWatcher`, with the `config-watch` feature.
+    ConfigReloadFailed { error: String },
+}
+
+/// The sending half of the events of a device, which also feeds any
+/// [`AudioEvents`][crate::AudioEvents] streams.
+#[derive(Clone)]
+pub(crate) struct EventSender {
+    sender: SyncSender<AudioEvent>,
+    #[cfg(feature = "async")]
+    pub(crate) streams: crate::asynchronous::Streams,
+}
+
+/// Creates a channel for sending [`AudioEvent`]s.
+pub(crate) fn channel() -> (EventSender, Receiver<AudioEvent>) {
+    let (sender, receiver) = mpsc::sync_channel(CAPACITY);
+    let sender = EventSender {
+        sender,
+        #[cfg(feature = "async")]
+        streams: Default::default(),
+    };
+    (sender, receiver)
+}
+
+/// Sends an event without waiting, dropping it if the channel is full or nobody is listening.
+pub(crate) fn send(sender: &EventSender, event: AudioEvent) {
+    #[cfg(feature = "async")]
+    sender.streams.send(&event);
+    let _ = sender.sender.try_send(event);
+}
@@ -0,0 +1,128 @@
+//! Filling gaps in the audio, when there's nothing to play where there should be.
+
+use std::time::Duration;
+
+use rg3d_sound::{context::SAMPLE_RATE, engine::SoundEngine};
+
+/// The level below which repeated audio stops, about -60dB.
+const INAUDIBLE: f32 = 0.001;
+/// The highest decay of [`UnderrunFill::Repeat`], so that the repeats always fade out.
+const MAX_DECAY: f32 = 0.99;
+
+/// What to play where audio is missing: when rendering a block panics, when an
+/// [`ExternalInput`][crate::ExternalInput] runs dry, or when a device being switched away from
+/// runs out of audio to fade out. Set it with [`OpenOptions::underrun_fill`] or
+/// [`Callback::set_underrun_fill`].
+///
+/// Cutting straight to silence pops, so the other strategies carry on from the audio played
+/// before the gap.
+///
+/// [`OpenOptions::underrun_fill`]: crate::OpenOptions::underrun_fill
+/// [`Callback::set_underrun_fill`]: crate::Callback::set_underrun_fill
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum UnderrunFill {
+    /// Plays silence straight away.
+    #[default]
+    Silence,
+    /// Plays the audio before the gap backwards, starting from the last frame played, so that the
+    /// waveform carries on smoothly, fading it out linearly to silence over the given time,
+    /// typically a few milliseconds. A fade longer than the block of audio kept from before the
+    /// gap plays it forwards again once it reaches the start, and so on, until it is silent.
+    Fade(Duration),
+    /// Repeats the last block of audio before the gap, turning each repeat down by `decay`, until
+    /// it is inaudible. When the fill is set, `decay` is clamped to between 0 and 0.99, so that
+    /// the repeats always fade out, and NaN is taken as 0.
+    Repeat { decay: f32 },
+}
+
+impl UnderrunFill {
+    /// Returns this fill with the decay of [`UnderrunFill::Repeat`] clamped, as it is when set.
+    pub(crate) fn clamped(self) -> Self {
+        match self {
+            Self::Repeat { decay } if decay.is_nan() => Self::Repeat { decay: 0.0 },
+            Self::Repeat { decay } => Self::Repeat {
+                decay: decay.clamp(0.0, MAX_DECAY),
+            },
+            fill => fill,
+        }
+    }
+}
+
+/// The audio played before a gap, and how far through filling the gap it is.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Filler {
+    /// Up to a block of the interleaved audio played before the gap.
+    history: Vec<f32>,
+    channels: usize,
+    /// The number of frames of the gap filled so far.
+    filled: usize,
+}
+
+impl Filler {
+    /// Remembers audio which was played, keeping up to a block of the most recent. Nothing is
+    /// remembered for [`UnderrunFill::Silence`].
+    pub(crate) fn record(
+        &mut self,
+        fill: UnderrunFill,
+        samples: impl IntoIterator<Item = f32>,
+        channels: usize,
+    ) {
+        if fill == UnderrunFill::Silence {
+            return;
+        }
+        if channels != self.channels || self.filled > 0 {
+            self.history.clear();
+            self.channels = channels;
+            self.filled = 0;
+        }
+        self.history.extend(samples);
+        let excess = self
+            .history
+            .len()
+            .saturating_sub(SoundEngine::render_buffer_len() * channels);
+        self.history.drain(..excess);
+    }
+
+    /// Fills `out`, which follows whatever was last recorded or filled, with interleaved frames
+    /// of `channels` channels.
+    pub(crate) fn fill(&mut self, fill: UnderrunFill, out: &mut [f32], channels: usize) {
+        out.fill(0.0);
+        let frames = self.history.len() / channels.max(1);
+        if channels != self.channels || frames == 0 {
+            return;
+        }
+        for frame in out.chunks_exact_mut(channels) {
+            let (index, gain) = match fill {
+                UnderrunFill::Silence => return,
+                UnderrunFill::Fade(duration) => {
+                    let len = (duration.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+                    if self.filled >= len {
+                        return;
+                    }
+                    let gain = 1.0 - self.filled as f32 / len as f32;
+                    // Backwards from the end of the history, then forwards from its start, and so
+                    // on, so that it never jumps.
+                    let pos = self.filled % (frames * 2);
+                    let index = if pos < frames {
+                        frames - 1 - pos
+                    } else {
+                        pos - frames
+                    };
+                    (index, gain)
+                }
+                UnderrunFill::Repeat { decay } => {
+                    let gain = decay.powi((self.filled / frames + 1) as i32);
+                    if gain < INAUDIBLE {
+                        return;
+                    }
+                    (self.filled % frames, gain)
+                }
+            };
+            let from = &self.history[index * channels..(index + 1) * channels];
+            for (out, &sample) in frame.iter_mut().zip(from) {
+                *out = sample * gain;
+            }
+            self.filled += 1;
+        }
+    }
+}
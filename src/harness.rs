@@ -0,0 +1,230 @@
+//! Golden-file tests of the whole pipeline, with the `test-harness` feature.
+//!
+//! A [`Harness`] opens a device with the given [`OpenOptions`] on SDL's dummy driver, negotiating
+//! its spec as a real device would, but runs the callback itself rather than leaving it to SDL, so
+//! that exactly the same audio is rendered on every run however busy the machine is. What it
+//! renders can then be compared against a golden file with [`compare_golden`], to catch changes
+//! to spec negotiation, mixing, routing or any of the processing of the output.
+//! # Example
+//! ```no_run
+//! use rg3d_sound::context::SoundContext;
+//! use rg3d_sound_sdl::{
+//!     harness::{self, Harness},
+//!     ChannelLayout, OpenOptions,
+//! };
+//!
+//! let sdl = sdl2::init().unwrap();
+//! let audio = sdl.audio().unwrap();
+//! let mut harness = Harness::open(
+//!     &audio,
+//!     OpenOptions::new().channel_layout(ChannelLayout::Surround51),
+//! )
+//! .unwrap();
+//! let context = SoundContext::new();
+//! harness::build_scene(&context);
+//! harness.sound().engine().lock().unwrap().add_context(context);
+//!
+//! let samples = harness.render(8192);
+//! harness::compare_golden("tests/golden/surround51.wav", harness.spec(), &samples, 1e-4).unwrap();
+//! ```
+
+use std::{env, fs, path::Path};
+
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use rg3d_sound::{
+    algebra::Vector3,
+    buffer::{DataSource, SoundBufferResource},
+    context::{SoundContext, SAMPLE_RATE},
+    source::{generic::GenericSourceBuilder, spatial::SpatialSourceBuilder, Status},
+};
+use sdl2::{audio::AudioCallback, AudioSubsystem};
+
+use crate::{ObtainedSpec, OpenOptions, SdlSound};
+
+/// The environment variable which, when set, makes [`compare_golden`] overwrite golden files
+/// with what was rendered, rather than comparing against them.
+pub const BLESS_VAR: &str = "RG3D_SOUND_SDL_BLESS";
+
+/// A device on the dummy driver whose callback is run by [`Harness::render`].
+pub struct Harness {
+    sound: SdlSound,
+}
+
+impl Harness {
+    /// Opens a device on the dummy driver with `options`, leaving it paused so that SDL never runs
+    /// the callback itself. On error, returns the SDL error, or a description of why the obtained
+    /// spec was rejected.
+    pub fn open(subsystem: &AudioSubsystem, options: &OpenOptions) -> Result<Self, String> {
+        let sound = options.clone().start_paused(true).open_dummy(subsystem)?;
+        Ok(Self { sound })
+    }
+
+    /// Returns the device, for attaching contexts to its engine and setting up its callback.
+    pub fn sound(&mut self) -> &mut SdlSound {
+        &mut self.sound
+    }
+
+    /// Makes the callback play as though the driver played the device at `rate`, in Hz, as
+    /// [`RateChangePolicy::Resample`][crate::RateChangePolicy::Resample] does once it notices the
+    /// change, so that [`Harness::render`] renders at that rate.
+    pub fn set_device_rate(&mut self, rate: u32) {
+        self.sound
+            .with_callback(|callback| callback.set_device_rate(rate));
+    }
+
+    /// Returns the spec SDL obtained for the device.
+    pub fn spec(&self) -> ObtainedSpec {
+        self.sound.obtained_spec()
+    }
+
+    /// Runs the callback until it has produced `frames` frames, a buffer of the obtained size at a
+    /// time, as SDL would, and returns them interleaved.
+    pub fn render(&mut self, frames: usize) -> Vec<f32> {
+        let spec = self.spec();
+        let channels = usize::from(spec.channels);
+        let mut buf = vec![0.0; usize::from(spec.samples) * channels];
+        let mut samples = Vec::with_capacity(frames * channels + buf.len());
+        while samples.len() < frames * channels {
+            self.sound
+                .with_callback(|callback| callback.callback(&mut buf));
+            samples.extend_from_slice(&buf);
+        }
+        samples.truncate(frames * channels);
+        samples
+    }
+}
+
+/// Adds the fixed scene used by the golden files to a context: a looping 440Hz sine wave played
+/// without positioning at half gain, and a burst of noise to the front left of the listener.
+/// # Panics
+/// This function will panic if `rg3d_sound` rejects the generated sounds.
+pub fn build_scene(context: &SoundContext) {
+    let sine = (0..SAMPLE_RATE)
+        .map(|i| (i as f32 * 440.0 / SAMPLE_RATE as f32 * std::f32::consts::TAU).sin())
+        .collect();
+    let mut seed = 0x9e37_79b9u32;
+    let noise = (0..SAMPLE_RATE / 10)
+        .map(|_| {
+            // xorshift32
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+        })
+        .collect();
+
+    let mut state = context.state();
+    let sine = GenericSourceBuilder::new()
+        .with_buffer(raw_buffer(sine))
+        .with_gain(0.5)
+        .with_looping(true)
+        .with_status(Status::Playing)
+        .build_source()
+        .unwrap();
+    state.add_source(sine);
+    let noise = GenericSourceBuilder::new()
+        .with_buffer(raw_buffer(noise))
+        .with_status(Status::Playing)
+        .build()
+        .unwrap();
+    let noise = SpatialSourceBuilder::new(noise)
+        .with_position(Vector3::new(-2.0, 0.0, 1.0))
+        .build_source();
+    state.add_source(noise);
+}
+
+fn raw_buffer(samples: Vec<f32>) -> SoundBufferResource {
+    SoundBufferResource::new_generic(DataSource::Raw {
+        sample_rate: SAMPLE_RATE as usize,
+        channel_count: 1,
+        samples,
+    })
+    .expect("Failed to create test sound")
+}
+
+/// Compares interleaved samples rendered with the given spec against the WAV file at `path`,
+/// failing if any sample differs by more than `tolerance`, or the spec or length differ, or the
+/// file doesn't exist. On failure, returns a description of the first difference.
+///
+/// If the [`BLESS_VAR`] environment variable is set, the samples are written to the file instead,
+/// as 32-bit float WAV, for committing as the new golden file.
+pub fn compare_golden(
+    path: impl AsRef<Path>,
+    spec: ObtainedSpec,
+    samples: &[f32],
+    tolerance: f32,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    if env::var_os(BLESS_VAR).is_some() {
+        return bless(path, spec, samples);
+    }
+    if !path.exists() {
+        return Err(format!(
+            "{} doesn't exist, set {} to create it",
+            path.display(),
+            BLESS_VAR
+        ));
+    }
+
+    let mut reader =
+        WavReader::open(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let golden_spec = reader.spec();
+    if golden_spec.channels != u16::from(spec.channels)
+        || golden_spec.sample_rate != spec.freq as u32
+    {
+        return Err(format!(
+            "Rendered {} channels at {}Hz, but {} has {} channels at {}Hz",
+            spec.channels,
+            spec.freq,
+            path.display(),
+            golden_spec.channels,
+            golden_spec.sample_rate
+        ));
+    }
+    let golden = reader
+        .samples::<f32>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if golden.len() != samples.len() {
+        return Err(format!(
+            "Rendered {} samples, but {} has {}",
+            samples.len(),
+            path.display(),
+            golden.len()
+        ));
+    }
+    let channels = usize::from(spec.channels);
+    match samples
+        .iter()
+        .zip(&golden)
+        .position(|(a, b)| (a - b).abs() > tolerance)
+    {
+        Some(i) => Err(format!(
+            "Frame {}, channel {} is {}, but {} has {}",
+            i / channels,
+            i % channels,
+            samples[i],
+            path.display(),
+            golden[i]
+        )),
+        None => Ok(()),
+    }
+}
+
+fn bless(path: &Path, spec: ObtainedSpec, samples: &[f32]) -> Result<(), String> {
+    let spec = WavSpec {
+        channels: spec.channels.into(),
+        sample_rate: spec.freq as u32,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    let error = |e: hound::Error| format!("Failed to write {}: {}", path.display(), e);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| error(e.into()))?;
+    }
+    let mut writer = WavWriter::create(path, spec).map_err(error)?;
+    for &sample in samples {
+        writer.write_sample(sample).map_err(error)?;
+    }
+    writer.finalize().map_err(error)
+}
@@ -0,0 +1,68 @@
+//! Loading and caching HRIR spheres for HRTF rendering.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use rg3d_sound::{
+    context::SAMPLE_RATE,
+    hrtf::{HrirSphere, HrtfError},
+};
+
+/// Loads an HRIR sphere from a file, resampled to [`SAMPLE_RATE`], for use with
+/// [`SdlSound::set_hrtf`][crate::SdlSound::set_hrtf].
+///
+/// Resampling a sphere is slow, so each processed sphere is cached by its path, and loading the
+/// same file again returns a copy of the cached sphere. On error, returns a description of why the
+/// sphere couldn't be loaded.
+/// # Example
+/// ```no_run
+/// let sphere = rg3d_sound_sdl::load_hrir_sphere("IRC_1002_C.bin").unwrap();
+/// ```
+pub fn load_hrir_sphere(path: impl AsRef<Path>) -> Result<HrirSphere, String> {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, HrirSphere>>> = OnceLock::new();
+
+    let path = path.as_ref();
+    let key = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    let cache = CACHE.get_or_init(Default::default);
+    if let Some(sphere) = cache.lock().unwrap().get(&key) {
+        return Ok(sphere.clone());
+    }
+    // Loaded without holding the lock, so that other loads don't wait behind this one. If the
+    // same file is loaded on two threads at once, the first sphere cached is kept.
+    let sphere = HrirSphere::from_file(path, SAMPLE_RATE).map_err(describe)?;
+    Ok(cache.lock().unwrap().entry(key).or_insert(sphere).clone())
+}
+
+/// Returns the HRIR sphere bundled with this crate, measured from subject 1002 of the IRCAM
+/// Listen database, resampled to [`SAMPLE_RATE`].
+///
+/// The sphere is processed the first time it's needed, and cached after that. As the sphere isn't
+/// loaded from a file, contexts rendering with it can't be saved with `rg3d_sound`'s visitor. The
+/// impulse responses are IRCAM's, and are distributed under the LISTEN database's own terms, given
+/// in the crate's NOTICE file, which applications using them should credit.
+/// # Example
+/// ```
+/// let sphere = rg3d_sound_sdl::bundled_hrir_sphere();
+/// assert!(sphere.len() > 0);
+/// ```
+#[cfg(feature = "bundled-hrir")]
+pub fn bundled_hrir_sphere() -> HrirSphere {
+    static SPHERE: OnceLock<HrirSphere> = OnceLock::new();
+    static DATA: &[u8] = include_bytes!("../data/IRC_1002_C.bin");
+
+    SPHERE
+        .get_or_init(|| HrirSphere::new(DATA, SAMPLE_RATE).expect("Bundled HRIR sphere is invalid"))
+        .clone()
+}
+
+/// Describes an error loading an HRIR sphere.
+fn describe(error: HrtfError) -> String {
+    match error {
+        HrtfError::IoError(e) => e.to_string(),
+        HrtfError::InvalidFileFormat => "Invalid HRIR sphere file".into(),
+        HrtfError::InvalidLength(len) => format!("Invalid HRIR length: {}", len),
+    }
+}
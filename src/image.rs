@@ -0,0 +1,116 @@
+//! Adjusting the stereo image of the output of a [`Callback`][crate::Callback], for players who
+//! need it to hear everything.
+
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_4, SQRT_2};
+
+use crate::{ChannelLayout, Speaker};
+
+/// How each pair of left and right channels is mixed, as `[left from left, left from right,
+/// right from left, right from right]`.
+type Matrix = [f32; 4];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0];
+
+/// The adjustments made to the stereo image of the output, applied to each pair of left and
+/// right speakers. Changes are ramped, so that moving a slider doesn't click.
+#[derive(Debug, Clone)]
+pub(crate) struct StereoImage {
+    /// The balance, from -1 for fully left to 1 for fully right.
+    pub(crate) balance: f32,
+    /// Whether each pair is folded to mono before the balance is applied.
+    pub(crate) mono: bool,
+    /// Whether the left and right channels of each pair are swapped, before the balance is
+    /// applied so that it still refers to the speakers.
+    pub(crate) swapped: bool,
+    /// The matrices being ramped from and to, and the number of frames the ramp takes, and has
+    /// left.
+    from: Matrix,
+    to: Matrix,
+    len: usize,
+    left: usize,
+}
+
+impl Default for StereoImage {
+    fn default() -> Self {
+        Self {
+            balance: 0.0,
+            mono: false,
+            swapped: false,
+            from: IDENTITY,
+            to: IDENTITY,
+            len: 0,
+            left: 0,
+        }
+    }
+}
+
+impl StereoImage {
+    /// Returns the matrix the current adjustments add up to.
+    fn matrix(&self) -> Matrix {
+        let mut matrix = IDENTITY;
+        if self.mono {
+            // Summing correlated channels doubles their level, so each is turned down by 3dB.
+            matrix = [FRAC_1_SQRT_2; 4];
+        }
+        if self.swapped {
+            matrix = multiply([0.0, 1.0, 1.0, 0.0], matrix);
+        }
+        if self.balance != 0.0 {
+            // Constant power, so the centre is at unity gain and either side is 3dB up at most.
+            let angle = (self.balance + 1.0) * FRAC_PI_4;
+            let balance = [angle.cos() * SQRT_2, 0.0, 0.0, angle.sin() * SQRT_2];
+            matrix = multiply(balance, matrix);
+        }
+        matrix
+    }
+
+    /// Returns the matrix applied to the next frame.
+    fn current(&self) -> Matrix {
+        if self.left == 0 {
+            return self.to;
+        }
+        let t = 1.0 - self.left as f32 / self.len as f32;
+        std::array::from_fn(|k| self.from[k] + (self.to[k] - self.from[k]) * t)
+    }
+
+    /// Applies the adjustments to a block of interleaved frames in the given layout, ramping
+    /// changes over `ramp` frames.
+    pub(crate) fn apply(&mut self, mix: &mut [f32], layout: ChannelLayout, ramp: usize) {
+        let target = self.matrix();
+        if target != self.to {
+            self.from = self.current();
+            self.to = target;
+            self.len = ramp;
+            self.left = ramp;
+        }
+        if self.left == 0 && self.to == IDENTITY {
+            return;
+        }
+        let speakers = layout.speakers();
+        for frame in mix.chunks_exact_mut(speakers.len()) {
+            let m = self.current();
+            self.left = self.left.saturating_sub(1);
+            for l in (0..speakers.len() - 1).filter(|&l| is_pair(&speakers[l..=l + 1])) {
+                let (left, right) = (frame[l], frame[l + 1]);
+                frame[l] = left * m[0] + right * m[1];
+                frame[l + 1] = left * m[2] + right * m[3];
+            }
+        }
+    }
+}
+
+/// Returns the matrix which applies `b`, then `a`.
+fn multiply(a: Matrix, b: Matrix) -> Matrix {
+    [
+        a[0] * b[0] + a[1] * b[2],
+        a[0] * b[1] + a[1] * b[3],
+        a[2] * b[0] + a[3] * b[2],
+        a[2] * b[1] + a[3] * b[3],
+    ]
+}
+
+/// Returns whether two adjacent speakers are a left speaker and the matching right one. SDL
+/// always orders the channels of each such pair left then right.
+fn is_pair(speakers: &[Speaker]) -> bool {
+    speakers[0].spread_gains() == [1.0, 0.0] && speakers[1].spread_gains() == [0.0, 1.0]
+}
@@ -0,0 +1,304 @@
+//! Mixing externally produced PCM audio into the output of a [`Callback`][crate::Callback].
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use rg3d_sound::context::SAMPLE_RATE;
+
+use crate::{fill::Filler, ramp::Ramp, UnderrunFill};
+
+/// The largest correction drift compensation makes to the rate an input is played at, as a
+/// fraction of the rate. Real clocks are within a few hundred parts per million of each other.
+const MAX_CORRECTION: f64 = 0.002;
+/// How strongly drift compensation corrects the rate for the error in the buffer level, in
+/// seconds, per second. Along with `INTEGRAL`, this settles in about a minute without
+/// overshooting.
+const PROPORTIONAL: f64 = 0.05;
+/// How quickly the estimate of the drift follows the error in the buffer level.
+const INTEGRAL: f64 = PROPORTIONAL * PROPORTIONAL / 4.0;
+/// How quickly the measured buffer level follows the actual level, per second. Smoothing this
+/// keeps the jitter of the producer from wobbling the pitch.
+const LEVEL_SMOOTHING: f64 = 0.2;
+
+/// A handle to a ring buffer of audio frames which is mixed into the output of a
+/// [`Callback`][crate::Callback], for audio that doesn't come from `rg3d_sound`, such as the
+/// soundtrack of a video decoded elsewhere.
+///
+/// Frames are pushed as interleaved `f32` samples at
+/// [`SAMPLE_RATE`][rg3d_sound::context::SAMPLE_RATE], with either one or two channels. The handle
+/// can be cloned and sent to any thread, and all clones refer to the same buffer.
+///
+/// When the callback needs more frames than have been pushed, the missing frames are filled as
+/// the callback's [`UnderrunFill`] says, with silence by default, and an underflow is counted.
+/// Playback of the input, both initially and after an underflow, waits until the buffer is at
+/// least half full, so that a producer which is only slightly too slow doesn't cause constant
+/// crackling.
+///
+/// If the frames come from a capture device, for example for voice chat, its clock and that of
+/// the playback device drift apart, so the buffer eventually overflows or runs dry. Turning on
+/// [`set_drift_compensation`][ExternalInput::set_drift_compensation] keeps the buffer half full
+/// by resampling the input very slightly.
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::ExternalInput;
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+///
+/// let video_audio = ExternalInput::new(2, 44_100);
+/// device.lock().add_input(video_audio.clone());
+/// device.resume();
+///
+/// // On the decoding thread:
+/// let decoded = vec![0.0; 2048];
+/// video_audio.push(&decoded);
+/// ```
+#[derive(Clone)]
+pub struct ExternalInput {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    channels: u8,
+    capacity: usize,
+    ring: Mutex<Ring>,
+    /// The gain as the bits of an `f32`.
+    gain: AtomicU32,
+    underflows: AtomicU64,
+}
+
+struct Ring {
+    frames: VecDeque<(f32, f32)>,
+    /// Whether playback is waiting for the buffer to refill after an underflow.
+    refilling: bool,
+    drift: Option<Drift>,
+    /// What fills the gaps when the buffer runs dry.
+    filler: Filler,
+    /// The frames played from the buffer in the current block, and the frames filling the gap
+    /// after them, kept to avoid allocating.
+    played: Vec<(f32, f32)>,
+    gap: Vec<f32>,
+    /// The gain as applied by the callback, ramping to the gain which was set.
+    gain: Ramp,
+}
+
+/// The state of drift compensation, which plays the input at whichever rate keeps the buffer
+/// half full.
+#[derive(Default)]
+struct Drift {
+    /// The smoothed buffer level, in frames, or `None` until playback starts.
+    level: Option<f64>,
+    /// The estimated rate of the producer relative to the callback, minus one.
+    estimate: f64,
+    /// The position of the next output frame between the first two buffered frames.
+    pos: f64,
+}
+
+impl ExternalInput {
+    /// Creates a new input with the given number of channels (1 or 2), whose buffer holds at most
+    /// `capacity` frames.
+    /// # Panics
+    /// This function will panic if `channels` is not 1 or 2, or `capacity` is zero.
+    pub fn new(channels: u8, capacity: usize) -> Self {
+        assert!(
+            channels == 1 || channels == 2,
+            "External inputs must be mono or stereo"
+        );
+        assert!(capacity > 0, "External input capacity must not be zero");
+        Self {
+            shared: Arc::new(Shared {
+                channels,
+                capacity,
+                ring: Mutex::new(Ring {
+                    frames: VecDeque::with_capacity(capacity),
+                    refilling: true,
+                    drift: None,
+                    filler: Filler::default(),
+                    played: Vec::new(),
+                    gap: Vec::new(),
+                    gain: Ramp::new(1.0),
+                }),
+                gain: AtomicU32::new(1.0f32.to_bits()),
+                underflows: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Pushes interleaved samples into the buffer. Returns the number of frames that were
+    /// accepted, which is less than provided if the buffer is full. A trailing partial frame is
+    /// ignored.
+    pub fn push(&self, samples: &[f32]) -> usize {
+        let mut ring = self.shared.ring.lock().unwrap();
+        let space = self.shared.capacity - ring.frames.len();
+        let before = ring.frames.len();
+        if self.shared.channels == 1 {
+            ring.frames
+                .extend(samples.iter().take(space).map(|&s| (s, s)));
+        } else {
+            ring.frames
+                .extend(samples.chunks_exact(2).take(space).map(|f| (f[0], f[1])));
+        }
+        ring.frames.len() - before
+    }
+
+    /// Returns the number of frames currently waiting to be played.
+    pub fn buffered(&self) -> usize {
+        self.shared.ring.lock().unwrap().frames.len()
+    }
+
+    /// Discards all buffered frames, for example after seeking the source.
+    pub fn clear(&self) {
+        let mut ring = self.shared.ring.lock().unwrap();
+        ring.frames.clear();
+        ring.refilling = true;
+        if let Some(drift) = &mut ring.drift {
+            drift.level = None;
+            drift.pos = 0.0;
+        }
+    }
+
+    /// Sets whether the input is resampled to follow the rate frames are pushed at, for frames
+    /// produced by a clock other than the playback device's, such as a capture device's.
+    pub fn set_drift_compensation(&self, enabled: bool) {
+        let mut ring = self.shared.ring.lock().unwrap();
+        if enabled != ring.drift.is_some() {
+            ring.drift = enabled.then(Drift::default);
+        }
+    }
+
+    /// Returns whether drift compensation is enabled.
+    pub fn drift_compensation(&self) -> bool {
+        self.shared.ring.lock().unwrap().drift.is_some()
+    }
+
+    /// Returns how much faster frames are estimated to be pushed than they are played, in parts
+    /// per million, or zero without drift compensation.
+    pub fn drift_ppm(&self) -> f64 {
+        let ring = self.shared.ring.lock().unwrap();
+        ring.drift
+            .as_ref()
+            .map_or(0.0, |drift| drift.estimate * 1e6)
+    }
+
+    /// Returns the number of channels samples are pushed with.
+    pub fn channels(&self) -> u8 {
+        self.shared.channels
+    }
+
+    /// Sets the gain this input is mixed at, ramping to it over the callback's
+    /// [gain ramp][crate::Callback::set_gain_ramp].
+    pub fn set_gain(&self, gain: f32) {
+        self.shared.gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns the gain this input is mixed at.
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.shared.gain.load(Ordering::Relaxed))
+    }
+
+    /// Returns the number of times the callback ran out of frames from this input.
+    pub fn underflows(&self) -> u64 {
+        self.shared.underflows.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether two handles refer to the same input.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// Mixes as many buffered frames as are available into `buf`, filling the rest as `fill`
+    /// says, and ramping changes to the gain over `ramp` frames.
+    pub(crate) fn mix_into(&self, buf: &mut [(f32, f32)], fill: UnderrunFill, ramp: usize) {
+        let mut ring = self.shared.ring.lock().unwrap();
+        let ring = &mut *ring;
+        ring.gain.set(self.gain(), ramp);
+        let gain = &mut ring.gain;
+        let played = &mut ring.played;
+        played.clear();
+        if !ring.refilling || ring.frames.len() >= self.shared.capacity / 2 {
+            ring.refilling = false;
+            played.resize(buf.len(), (0.0, 0.0));
+            let len = match &mut ring.drift {
+                Some(drift) => drift.mix_into(&mut ring.frames, self.shared.capacity, played),
+                None => {
+                    let len = buf.len().min(ring.frames.len());
+                    for (out, frame) in played.iter_mut().zip(ring.frames.drain(..len)) {
+                        *out = frame;
+                    }
+                    len
+                }
+            };
+            played.truncate(len);
+            if len < buf.len() {
+                ring.refilling = true;
+                self.shared.underflows.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        for (out, (left, right)) in buf.iter_mut().zip(played.iter()) {
+            let gain = gain.next();
+            out.0 += left * gain;
+            out.1 += right * gain;
+        }
+        if !played.is_empty() {
+            let samples = played.iter().flat_map(|&(left, right)| [left, right]);
+            ring.filler.record(fill, samples, 2);
+        }
+        if played.len() < buf.len() && fill != UnderrunFill::Silence {
+            let gap = &mut ring.gap;
+            gap.resize((buf.len() - played.len()) * 2, 0.0);
+            ring.filler.fill(fill, gap, 2);
+            for (out, frame) in buf[played.len()..].iter_mut().zip(gap.chunks_exact(2)) {
+                let gain = gain.next();
+                out.0 += frame[0] * gain;
+                out.1 += frame[1] * gain;
+            }
+        } else {
+            gain.skip(buf.len() - played.len());
+        }
+    }
+}
+
+impl Drift {
+    /// Mixes frames into `buf` at the rate which keeps the buffer half full, returning the number
+    /// of frames of `buf` that could be filled.
+    fn mix_into(
+        &mut self,
+        frames: &mut VecDeque<(f32, f32)>,
+        capacity: usize,
+        buf: &mut [(f32, f32)],
+    ) -> usize {
+        let seconds = buf.len() as f64 / SAMPLE_RATE as f64;
+        // The level halfway through playing this block, which is about the average level.
+        let actual = frames.len() as f64 - buf.len() as f64 / 2.0;
+        let level = self.level.get_or_insert(actual);
+        *level += (actual - *level) * (LEVEL_SMOOTHING * seconds).min(1.0);
+        let error = (*level - capacity as f64 / 2.0) / SAMPLE_RATE as f64;
+        self.estimate =
+            (self.estimate + error * INTEGRAL * seconds).clamp(-MAX_CORRECTION, MAX_CORRECTION);
+        let rate =
+            1.0 + (self.estimate + error * PROPORTIONAL).clamp(-MAX_CORRECTION, MAX_CORRECTION);
+
+        for (i, out) in buf.iter_mut().enumerate() {
+            let (a, b) = match (frames.front(), frames.get(1)) {
+                (Some(&a), Some(&b)) => (a, b),
+                _ => return i,
+            };
+            let frac = self.pos as f32;
+            out.0 += a.0 + (b.0 - a.0) * frac;
+            out.1 += a.1 + (b.1 - a.1) * frac;
+            self.pos += rate;
+            while self.pos >= 1.0 {
+                frames.pop_front();
+                self.pos -= 1.0;
+            }
+        }
+        buf.len()
+    }
+}
@@ -0,0 +1,115 @@
+//! Speaker layouts of multi-channel playback devices.
+
+use std::f32::consts::FRAC_1_SQRT_2;
+
+/// A speaker which a channel of a playback device is sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Speaker {
+    /// The front left speaker.
+    FrontLeft,
+    /// The front right speaker.
+    FrontRight,
+    /// The front center speaker.
+    FrontCenter,
+    /// The low frequency effects speaker, or subwoofer.
+    LowFrequency,
+    /// The rear left speaker.
+    BackLeft,
+    /// The rear right speaker.
+    BackRight,
+    /// The left surround speaker, beside the listener.
+    SideLeft,
+    /// The right surround speaker, beside the listener.
+    SideRight,
+}
+
+impl Speaker {
+    /// Returns how much of the left and right channels of a stereo signal this speaker plays
+    /// when the signal is spread over every speaker. The low frequency speaker plays nothing.
+    pub fn spread_gains(self) -> [f32; 2] {
+        match self {
+            Self::FrontLeft | Self::BackLeft | Self::SideLeft => [1.0, 0.0],
+            Self::FrontRight | Self::BackRight | Self::SideRight => [0.0, 1.0],
+            Self::FrontCenter => [FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+            Self::LowFrequency => [0.0, 0.0],
+        }
+    }
+}
+
+/// The channel layout of a playback device, using the channel orders SDL defines.
+///
+/// `rg3d_sound` always renders stereo, so on devices with more channels, the output of each engine
+/// is spread across the speakers with a [`MixMatrix`][crate::MixMatrix].
+/// # Example
+/// ```
+/// use rg3d_sound_sdl::{ChannelLayout, Speaker};
+///
+/// let layout = ChannelLayout::from_channels(6).unwrap();
+/// assert_eq!(layout, ChannelLayout::Surround51);
+/// assert_eq!(layout.speakers()[2], Speaker::FrontCenter);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelLayout {
+    /// One channel: front center.
+    Mono,
+    /// Two channels: front left, front right.
+    #[default]
+    Stereo,
+    /// Four channels: front left, front right, back left, back right.
+    Quad,
+    /// Six channels: front left, front right, front center, low frequency, side left, side
+    /// right.
+    Surround51,
+    /// Eight channels: front left, front right, front center, low frequency, back left, back
+    /// right, side left, side right.
+    Surround71,
+}
+
+impl ChannelLayout {
+    /// Returns the layout SDL uses for the given number of channels, or `None` if this crate
+    /// doesn't support it.
+    pub fn from_channels(channels: u8) -> Option<Self> {
+        match channels {
+            1 => Some(Self::Mono),
+            2 => Some(Self::Stereo),
+            4 => Some(Self::Quad),
+            6 => Some(Self::Surround51),
+            8 => Some(Self::Surround71),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of channels in this layout.
+    pub fn channels(self) -> u8 {
+        self.speakers().len() as u8
+    }
+
+    /// Returns the speaker each channel is sent to, in the order the channels are interleaved.
+    pub fn speakers(self) -> &'static [Speaker] {
+        use Speaker::*;
+        match self {
+            Self::Mono => &[FrontCenter],
+            Self::Stereo => &[FrontLeft, FrontRight],
+            Self::Quad => &[FrontLeft, FrontRight, BackLeft, BackRight],
+            Self::Surround51 => &[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                SideLeft,
+                SideRight,
+            ],
+            Self::Surround71 => &[
+                FrontLeft,
+                FrontRight,
+                FrontCenter,
+                LowFrequency,
+                BackLeft,
+                BackRight,
+                SideLeft,
+                SideRight,
+            ],
+        }
+    }
+}
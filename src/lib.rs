@@ -9,11 +9,7 @@
 //! use std::{fs::File, io::BufReader, thread, time::Duration};
 //! # use std::error::Error;
 //!
-//! use rg3d_sound::{
-//! buffer::{DataSource, SoundBufferResource},
-//! context::SoundContext,
-//! source::{generic::GenericSourceBuilder, Status},
-//! };
+//! use rg3d_sound_sdl::prelude::*;
 //!
 //!# fn main() -> Result<(), Box<dyn Error>> {
 //! let sdl = sdl2::init()?;
@@ -42,15 +38,150 @@
 //! # }
 //! ```
 
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use rg3d_sound::engine::SoundEngine;
-use sdl2::audio::{AudioCallback, AudioDevice, AudioFormat, AudioSpecDesired};
+use sdl2::audio::{AudioDevice, AudioSpecDesired};
+
+#[cfg(feature = "async")]
+mod asynchronous;
+mod callback;
+#[cfg(feature = "capi")]
+mod capi;
+mod capture;
+mod config;
+#[cfg(feature = "config-watch")]
+mod config_watch;
+mod crossfade;
+mod driver;
+mod dsp;
+mod duck;
+mod error;
+mod event;
+mod fill;
+#[cfg(feature = "test-harness")]
+pub mod harness;
+#[cfg(feature = "hrir")]
+mod hrir;
+mod image;
+mod input;
+mod layout;
+mod loopback;
+mod loudness;
+mod matrix;
+mod meter;
+mod multi;
+mod network;
+mod null;
+mod options;
+pub mod prelude;
+mod priority;
+mod ramp;
+mod rate;
+mod reconnect;
+mod recorder;
+mod renderer;
+mod router;
+mod rumble;
+mod rwops;
+mod selector;
+#[doc(hidden)]
+pub mod simd;
+mod sound;
+mod spec;
+#[cfg(feature = "parking_lot")]
+pub mod sync;
+mod telemetry;
+mod tone;
+mod varispeed;
+mod watchdog;
+
+#[cfg(feature = "async")]
+pub use asynchronous::AudioEvents;
+pub use callback::{Callback, EngineId, DEFAULT_GAIN_RAMP, DEFAULT_HIGH_PASS};
+pub use capture::{open_loopback_capture, CaptureCallback, LoopbackCapture};
+pub use config::{AudioConfig, ConfigField};
+#[cfg(feature = "config-watch")]
+pub use config_watch::ConfigWatcher;
+pub use duck::{Ducking, Sidechain};
+pub use error::FallbackError;
+pub use event::AudioEvent;
+pub use fill::UnderrunFill;
+#[cfg(feature = "bundled-hrir")]
+pub use hrir::bundled_hrir_sphere;
+#[cfg(feature = "hrir")]
+pub use hrir::load_hrir_sphere;
+pub use input::ExternalInput;
+pub use layout::{ChannelLayout, Speaker};
+pub use loopback::Loopback;
+pub use loudness::{LoudnessMeter, LoudnessNormalizer};
+pub use matrix::{LfeMode, MixMatrix};
+pub use meter::LevelMeter;
+pub use multi::{open_multi, BroadcastDevice, BroadcastReader};
+pub use network::NetworkSink;
+pub use options::{AllowedChanges, OpenOptions, SpecMismatchPolicy};
+pub use rate::RateChangePolicy;
+pub use reconnect::ReconnectPolicy;
+pub use recorder::{Recorder, WavFormat};
+pub use renderer::BlockRenderer;
+pub use router::{ContextRouter, OutputId};
+pub use rumble::Rumble;
+pub use rwops::RwopsReader;
+pub use selector::DeviceSelector;
+pub use sound::SdlSound;
+pub use spec::ObtainedSpec;
+#[cfg(feature = "metrics")]
+pub use telemetry::describe_metrics;
+pub use tone::{InjectionMode, TestSignal};
+pub use watchdog::WatchdogPolicy;
+
+#[cfg(not(feature = "parking_lot"))]
+use std::sync::Mutex as Lock;
+#[cfg(feature = "parking_lot")]
+use sync::Mutex as Lock;
+
+/// The lock around each engine, which is [`std::sync::Mutex`], or with the `parking_lot`
+/// feature, [`sync::Mutex`], which has the same locking API.
+///
+/// Measured with `benches/engine_lock.rs` on x86_64 Linux, locking and unlocking an uncontended
+/// engine takes about 17ns with either lock, against 3.4µs for the callback to play a block of an
+/// empty engine, so the lock costs the callback next to nothing either way. `parking_lot`'s lock
+/// is never poisoned, though, so a panic while the game holds the engine doesn't make later
+/// locks fail, and it's cheaper to wait for when contended.
+pub type EngineMutex<T> = Lock<T>;
+
+/// A shared handle to a [`SoundEngine`], as returned by [`new_engine`].
+pub type EngineHandle = Arc<EngineMutex<SoundEngine>>;
+
+/// Creates an engine without a device, as with [`SoundEngine::without_device`], but locked by
+/// an [`EngineMutex`] so that it can be given to this crate with or without the `parking_lot`
+/// feature.
+/// # Example
+/// ```
+/// let engine = rg3d_sound_sdl::new_engine();
+/// engine.lock().unwrap().set_master_gain(0.5);
+/// ```
+pub fn new_engine() -> EngineHandle {
+    #[cfg(not(feature = "parking_lot"))]
+    return SoundEngine::without_device();
+    #[cfg(feature = "parking_lot")]
+    {
+        let engine = std::sync::Arc::try_unwrap(SoundEngine::without_device())
+            .unwrap_or_else(|_| unreachable!("The engine was just created"))
+            .into_inner()
+            .unwrap();
+        Arc::new(EngineMutex::new(engine))
+    }
+}
 
 /// Opens a new audio device.
 ///
+/// `device` is the name of the device to open, or `None` for the default device. Use
+/// [`open_selector`] to open a device by index, or from a selector string.
+///
 /// On success, returns both the SDL [`AudioDevice`], and a handle to a
-/// [`SoundEngine`] which will drive the device. On error, returns the SDL error.
+/// [`SoundEngine`] which will drive the device. On error, returns the SDL error. The device starts
+/// paused; use [`OpenOptions`] for more control over how it is opened.
 /// # Example
 /// ```no_run
 /// let sdl = sdl2::init().unwrap();
@@ -61,32 +192,62 @@ use sdl2::audio::{AudioCallback, AudioDevice, AudioFormat, AudioSpecDesired};
 pub fn open<'a>(
     subsystem: &sdl2::AudioSubsystem,
     device: impl Into<Option<&'a str>>,
-) -> Result<(Arc<Mutex<SoundEngine>>, AudioDevice<Callback>), String> {
-    let desired = desired_spec();
-    let engine = SoundEngine::without_device();
-    let callback_engine = Arc::clone(&engine);
-
-    subsystem
-        .open_playback(device, &desired, |obtained| {
-            assert_eq!(
-                obtained.freq as u32,
-                rg3d_sound::context::SAMPLE_RATE,
-                "Invalid sample rate"
-            );
-            assert_eq!(obtained.channels, 2, "Invalid number of channels");
-            assert_eq!(
-                obtained.format,
-                AudioFormat::f32_sys(),
-                "Invalid sample format"
-            );
-            assert_eq!(
-                obtained.samples as usize,
-                SoundEngine::render_buffer_len(),
-                "Invalid buffer size"
-            );
-            Callback::new(callback_engine)
-        })
-        .map(|dev| (engine, dev))
+) -> Result<(EngineHandle, AudioDevice<Callback>), String> {
+    let device = match device.into() {
+        Some(device) => DeviceSelector::Name(device.into()),
+        None => DeviceSelector::Default,
+    };
+    open_selector(subsystem, device)
+}
+
+/// Opens the audio device chosen by a [`DeviceSelector`], which can be parsed from a selector
+/// string such as `"index:1"`, as [`open`] does. On error, returns the SDL error.
+/// # Example
+/// ```no_run
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let device = "index:1".parse().unwrap();
+/// let (engine, device) = rg3d_sound_sdl::open_selector(&audio, device).unwrap();
+/// device.resume();
+/// ```
+pub fn open_selector(
+    subsystem: &sdl2::AudioSubsystem,
+    device: DeviceSelector,
+) -> Result<(EngineHandle, AudioDevice<Callback>), String> {
+    OpenOptions::new()
+        .device(device)
+        .open(subsystem)
+        .map(SdlSound::into_parts)
+}
+
+/// Tries to open each of the given devices in order, and returns the first which succeeds. On
+/// error, returns the error each device failed with.
+///
+/// This lets a game keep running when the player's preferred device is broken, by falling back to
+/// the default device, and finally to SDL's dummy driver. Use
+/// [`OpenOptions::open_with_fallbacks`] for more control over how the devices are opened.
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::DeviceSelector;
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let sound = rg3d_sound_sdl::open_with_fallbacks(
+///     &audio,
+///     &[
+///         DeviceSelector::Name("Bluetooth Headset".into()),
+///         DeviceSelector::Default,
+///         DeviceSelector::Dummy,
+///     ],
+/// )
+/// .unwrap();
+/// sound.resume();
+/// ```
+pub fn open_with_fallbacks(
+    subsystem: &sdl2::AudioSubsystem,
+    devices: &[DeviceSelector],
+) -> Result<SdlSound, FallbackError> {
+    OpenOptions::new().open_with_fallbacks(subsystem, devices)
 }
 
 /// Obtain the desired SDL audio parameters for use with `rg3d_sound`. This is used internally by
@@ -114,28 +275,34 @@ pub fn desired_spec() -> AudioSpecDesired {
     }
 }
 
-/// An [`AudioCallback`] used to feed the SDL audio device with rendered audio from a
-/// [`SoundEngine`]
-pub struct Callback {
-    engine: Arc<Mutex<SoundEngine>>,
-}
-
-impl Callback {
-    /// Create a new `Callback` from an existing [`SoundEngine`]. The engine must be opened with
-    /// [`SoundEngine::without_device`] so that the manual rendering functions can be used.
-    pub fn new(engine: Arc<Mutex<SoundEngine>>) -> Self {
-        Self { engine }
-    }
-}
-
-impl AudioCallback for Callback {
-    type Channel = f32;
-
-    fn callback(&mut self, buf: &mut [Self::Channel]) {
-        let buf = to_tuple_slice(buf);
-        let mut engine = self.engine.lock().unwrap();
-        engine.render(buf);
-    }
+/// Splits a slice of interleaved stereo samples into frames, each holding a left and right sample.
+///
+/// If the slice has an odd number of elements, the trailing sample doesn't belong to any frame. It
+/// is set to zero, so that it plays as silence, and is left out of the returned frames.
+/// # Example
+/// ```
+/// let mut samples = [0.1, 0.2, 0.3, 0.4];
+/// let frames = rg3d_sound_sdl::frames_mut(&mut samples);
+/// assert_eq!(frames, &[[0.1, 0.2], [0.3, 0.4]]);
+///
+/// frames[1] = [0.5, 0.6];
+/// assert_eq!(samples, [0.1, 0.2, 0.5, 0.6]);
+/// ```
+/// An odd length slice has its trailing sample silenced:
+/// ```
+/// let mut samples = [0.1, 0.2, 0.3];
+/// let frames = rg3d_sound_sdl::frames_mut(&mut samples);
+/// assert_eq!(frames, &[[0.1, 0.2]]);
+/// assert_eq!(samples, [0.1, 0.2, 0.0]);
+/// ```
+/// ```
+/// let mut samples: [f32; 0] = [];
+/// assert!(rg3d_sound_sdl::frames_mut(&mut samples).is_empty());
+/// ```
+pub fn frames_mut(samples: &mut [f32]) -> &mut [[f32; 2]] {
+    let (frames, trailing) = samples.split_at_mut(samples.len() & !1);
+    trailing.fill(0.0);
+    bytemuck::cast_slice_mut(frames)
 }
 
 /// Converts a slice of [`f32`] values, of even length, to a slice of `(f32, f32)` tuples. The
@@ -145,6 +312,9 @@ impl AudioCallback for Callback {
 ///
 /// This crate also staticly asserts that the alignment and size of `(f32, f32)` and `[f32; 2]` are
 /// identical.
+#[deprecated(note = "use the safe `frames_mut` instead")]
+// `is_multiple_of` would raise the MSRV to 1.87.
+#[allow(clippy::manual_is_multiple_of)]
 pub fn to_tuple_slice(slice: &mut [f32]) -> &mut [(f32, f32)] {
     let ptr = slice.as_mut_ptr();
     let len = slice.len();
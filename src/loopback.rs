@@ -0,0 +1,147 @@
+//! Feeding the final output of a [`Callback`][crate::Callback] back into `rg3d_sound`.
+
+use std::{
+    collections::VecDeque,
+    fmt, mem,
+    sync::{Arc, Mutex, Weak},
+};
+
+use rg3d_sound::{
+    buffer::{streaming::StreamingBuffer, DataSource, RawStreamingDataSource},
+    context::SAMPLE_RATE,
+    engine::SoundEngine,
+};
+
+/// A tap on the final mixed output of a [`Callback`][crate::Callback], which can be played back
+/// through a `rg3d_sound` source using [`Loopback::data_source`].
+///
+/// This allows the game's own audio to be heard "in the world", for example through an in-game
+/// radio or security camera, with spatialization and filtering applied.
+///
+/// Streaming buffers in `rg3d_sound` read a whole block of
+/// [`STREAM_SAMPLE_COUNT`][StreamingBuffer::STREAM_SAMPLE_COUNT] frames at a time, so the
+/// looped back audio lags the output by a little over a second. If the source playing the
+/// loopback is itself audible in the output, it will also hear itself, so attenuate it
+/// accordingly to avoid runaway feedback.
+/// # Example
+/// ```no_run
+/// use rg3d_sound::{
+///     buffer::SoundBufferResource,
+///     context::SoundContext,
+///     source::{generic::GenericSourceBuilder, Status},
+/// };
+/// use rg3d_sound_sdl::Loopback;
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+///
+/// let loopback = Loopback::new();
+/// device.lock().add_loopback(loopback.clone());
+/// device.resume();
+///
+/// let ctx = SoundContext::new();
+/// engine.lock().unwrap().add_context(ctx.clone());
+/// let buffer = SoundBufferResource::new_streaming(loopback.data_source()).unwrap();
+/// let source = GenericSourceBuilder::new()
+///     .with_buffer(buffer)
+///     .with_status(Status::Playing)
+///     .with_gain(0.3)
+///     .build_source()
+///     .unwrap();
+/// ctx.state().add_source(source);
+/// ```
+#[derive(Clone, Default)]
+pub struct Loopback {
+    readers: Arc<Mutex<Vec<Weak<Ring>>>>,
+}
+
+/// The interleaved samples waiting to be read by a single [`LoopbackSource`].
+type Ring = Mutex<VecDeque<f32>>;
+
+impl Loopback {
+    /// Creates a new loopback tap. It will receive audio once added to a callback with
+    /// [`Callback::add_loopback`][crate::Callback::add_loopback].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new streaming [`DataSource`] which plays the audio written to this tap. Each data
+    /// source reads its own copy of the output, so any number of them may be created.
+    pub fn data_source(&self) -> DataSource {
+        // Start with enough silence that a streaming buffer reading a whole block never runs dry
+        // while the next block of output is still being rendered.
+        let delay = (StreamingBuffer::STREAM_SAMPLE_COUNT + SoundEngine::render_buffer_len()) * 2;
+        let ring = Arc::new(Mutex::new(VecDeque::from(vec![0.0; delay])));
+        self.readers.lock().unwrap().push(Arc::downgrade(&ring));
+        DataSource::RawStreaming(Box::new(LoopbackSource {
+            ring,
+            local: VecDeque::new(),
+        }))
+    }
+
+    /// Returns whether two handles refer to the same tap.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.readers, &other.readers)
+    }
+
+    /// Copies output frames to every data source created from this tap, forgetting about any
+    /// which have been dropped, without waiting for any which are being read from or created.
+    pub(crate) fn write(&self, buf: &[(f32, f32)]) {
+        // Bound each reader so a source which is never played can't grow without limit.
+        let capacity =
+            (StreamingBuffer::STREAM_SAMPLE_COUNT + SoundEngine::render_buffer_len()) * 4;
+        let Ok(mut readers) = self.readers.try_lock() else {
+            return;
+        };
+        readers.retain(|reader| {
+            let ring = match reader.upgrade() {
+                Some(ring) => ring,
+                None => return false,
+            };
+            if let Ok(mut ring) = ring.try_lock() {
+                ring.extend(buf.iter().flat_map(|&(left, right)| [left, right]));
+                let excess = ring.len().saturating_sub(capacity);
+                ring.drain(..excess);
+            }
+            true
+        });
+    }
+}
+
+/// The [`RawStreamingDataSource`] created by [`Loopback::data_source`].
+struct LoopbackSource {
+    ring: Arc<Ring>,
+    /// The samples taken from the ring which haven't been read yet, so that the ring is only
+    /// locked once per refill rather than for every sample.
+    local: VecDeque<f32>,
+}
+
+impl fmt::Debug for LoopbackSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoopbackSource").finish_non_exhaustive()
+    }
+}
+
+impl Iterator for LoopbackSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.local.is_empty() {
+            // Swapped rather than drained, so that the ring keeps an allocation to write to.
+            mem::swap(&mut *self.ring.lock().unwrap(), &mut self.local);
+        }
+        // The output never ends, so play silence rather than stopping the source if it runs dry.
+        Some(self.local.pop_front().unwrap_or(0.0))
+    }
+}
+
+impl RawStreamingDataSource for LoopbackSource {
+    fn sample_rate(&self) -> usize {
+        SAMPLE_RATE as usize
+    }
+
+    fn channel_count(&self) -> usize {
+        2
+    }
+}
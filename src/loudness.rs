@@ -0,0 +1,285 @@
+//! Measuring and normalizing the loudness of the output of a [`Callback`][crate::Callback].
+
+use std::f64::consts::PI;
+
+use rg3d_sound::context::SAMPLE_RATE;
+
+use crate::{dsp::Biquad, ChannelLayout, Speaker};
+
+/// The loudness below which blocks are ignored entirely, in LUFS.
+const ABSOLUTE_GATE: f32 = -70.0;
+/// How far below the ungated loudness blocks are ignored, in LU.
+const RELATIVE_GATE: f32 = -10.0;
+/// The width of each bin of the block loudness histogram, in LU.
+const BIN_WIDTH: f32 = 0.1;
+/// The number of histogram bins, covering -70 to +5 LUFS.
+const BINS: usize = 750;
+/// The number of frames blocks are measured over: 100ms, with each 400ms gating block made of
+/// four of them.
+const HOP_FRAMES: usize = SAMPLE_RATE as usize / 10;
+
+/// Measures the loudness of the output, as defined by ITU-R BS.1770 and EBU R128.
+///
+/// The meter reports the momentary loudness of the last 400ms, and the integrated loudness of
+/// everything measured since it was created or [reset][LoudnessMeter::reset], in LUFS.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    /// The K-weighting filters of each channel.
+    filters: Vec<[Biquad; 2]>,
+    /// The sum of the weighted squared samples of the current hop.
+    hop_sum: f64,
+    hop_frames: usize,
+    /// The mean power of the last four hops, oldest first.
+    hops: [f64; 4],
+    hops_measured: usize,
+    /// The number of gating blocks and their total power in each loudness bin.
+    histogram: Vec<(u64, f64)>,
+    momentary: Option<f32>,
+    integrated: Option<f32>,
+}
+
+impl LoudnessMeter {
+    /// Creates a meter which hasn't measured anything.
+    pub fn new() -> Self {
+        Self {
+            filters: Vec::new(),
+            hop_sum: 0.0,
+            hop_frames: 0,
+            hops: [0.0; 4],
+            hops_measured: 0,
+            histogram: vec![(0, 0.0); BINS],
+            momentary: None,
+            integrated: None,
+        }
+    }
+
+    /// Returns the loudness of the last 400ms, or `None` if less than that has been measured.
+    pub fn momentary(&self) -> Option<f32> {
+        self.momentary
+    }
+
+    /// Returns the gated loudness of everything measured so far, or `None` if nothing louder than
+    /// -70 LUFS has been measured.
+    pub fn integrated(&self) -> Option<f32> {
+        self.integrated
+    }
+
+    /// Forgets everything measured so far, for example when a new track starts.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Measures interleaved frames in the given layout.
+    pub(crate) fn measure(&mut self, frames: &[f32], layout: ChannelLayout) {
+        let speakers = layout.speakers();
+        if self.filters.len() != speakers.len() {
+            self.filters = vec![k_weighting(); speakers.len()];
+        }
+        for frame in frames.chunks_exact(speakers.len()) {
+            for ((&sample, filters), &speaker) in frame.iter().zip(&mut self.filters).zip(speakers)
+            {
+                let [shelf, high_pass] = filters;
+                let weighted = high_pass.process(shelf.process(sample)) as f64;
+                self.hop_sum += channel_weight(speaker) * weighted * weighted;
+            }
+            self.hop_frames += 1;
+            if self.hop_frames == HOP_FRAMES {
+                self.finish_hop();
+            }
+        }
+    }
+
+    /// Adds the gating block ending with the current hop.
+    fn finish_hop(&mut self) {
+        self.hops.rotate_left(1);
+        self.hops[3] = self.hop_sum / HOP_FRAMES as f64;
+        self.hop_sum = 0.0;
+        self.hop_frames = 0;
+        self.hops_measured += 1;
+        if self.hops_measured < self.hops.len() {
+            return;
+        }
+
+        let power = self.hops.iter().sum::<f64>() / self.hops.len() as f64;
+        let loudness = loudness(power);
+        self.momentary = Some(loudness);
+        if loudness > ABSOLUTE_GATE {
+            let bin = (((loudness - ABSOLUTE_GATE) / BIN_WIDTH) as usize).min(BINS - 1);
+            self.histogram[bin].0 += 1;
+            self.histogram[bin].1 += power;
+            self.integrated = self.gated_loudness();
+        }
+    }
+
+    /// Computes the integrated loudness from the histogram, applying the relative gate.
+    fn gated_loudness(&self) -> Option<f32> {
+        let mean = |bins: &[(u64, f64)]| {
+            let (count, power) = bins.iter().fold((0, 0.0), |(count, power), bin| {
+                (count + bin.0, power + bin.1)
+            });
+            (count > 0).then(|| power / count as f64)
+        };
+        let threshold = loudness(mean(&self.histogram)?) + RELATIVE_GATE;
+        let first = ((threshold - ABSOLUTE_GATE) / BIN_WIDTH).max(0.0) as usize;
+        mean(&self.histogram[first.min(BINS)..]).map(loudness)
+    }
+}
+
+impl Default for LoudnessMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Slowly adjusts the gain of the output so that its integrated loudness approaches a target,
+/// so that music of very different loudness can be played without tuning the gain of each track.
+///
+/// The correction is limited to [`LoudnessNormalizer::set_max_gain`] in either direction, and
+/// changes by at most [`LoudnessNormalizer::set_correction_rate`] per second, so that changes in
+/// gain aren't obvious. Add a normalizer to a callback with
+/// [`Callback::set_loudness_normalizer`][crate::Callback::set_loudness_normalizer].
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::LoudnessNormalizer;
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+/// device.lock().set_loudness_normalizer(LoudnessNormalizer::new(-16.0));
+/// device.resume();
+///
+/// // Later, when the next track starts:
+/// let mut callback = device.lock();
+/// if let Some(normalizer) = callback.loudness_normalizer_mut() {
+///     normalizer.meter_mut().reset();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LoudnessNormalizer {
+    meter: LoudnessMeter,
+    target: f32,
+    max_gain: f32,
+    rate: f32,
+    /// The gain currently applied, in dB.
+    gain: f32,
+}
+
+impl LoudnessNormalizer {
+    /// Creates a normalizer which aims for the given integrated loudness, in LUFS. The correction
+    /// is limited to 12dB, changing by up to 1dB per second.
+    pub fn new(target: f32) -> Self {
+        Self {
+            meter: LoudnessMeter::new(),
+            target,
+            max_gain: 12.0,
+            rate: 1.0,
+            gain: 0.0,
+        }
+    }
+
+    /// Sets the integrated loudness to aim for, in LUFS.
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    /// Returns the integrated loudness aimed for, in LUFS.
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// Sets the largest correction applied in either direction, in dB.
+    pub fn set_max_gain(&mut self, max_gain: f32) {
+        self.max_gain = max_gain.abs();
+    }
+
+    /// Sets how quickly the correction may change, in dB per second.
+    pub fn set_correction_rate(&mut self, rate: f32) {
+        self.rate = rate.abs();
+    }
+
+    /// Returns the correction currently applied, in dB.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Returns the meter measuring the loudness of the output before correction.
+    pub fn meter(&self) -> &LoudnessMeter {
+        &self.meter
+    }
+
+    /// Returns the meter mutably, for example to reset it.
+    pub fn meter_mut(&mut self) -> &mut LoudnessMeter {
+        &mut self.meter
+    }
+
+    /// Measures and corrects a block of interleaved frames in the given layout.
+    pub(crate) fn process(&mut self, frames: &mut [f32], layout: ChannelLayout) {
+        self.meter.measure(frames, layout);
+
+        let channels = layout.channels() as usize;
+        let len = frames.len() / channels;
+        let wanted = match self.meter.integrated() {
+            Some(loudness) => (self.target - loudness).clamp(-self.max_gain, self.max_gain),
+            None => self.gain,
+        };
+        let max_step = self.rate * len as f32 / SAMPLE_RATE as f32;
+        let start = db_to_gain(self.gain);
+        self.gain += (wanted - self.gain).clamp(-max_step, max_step);
+        let end = db_to_gain(self.gain);
+
+        for (i, frame) in frames.chunks_exact_mut(channels).enumerate() {
+            let gain = start + (end - start) * i as f32 / len as f32;
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+    }
+}
+
+/// Converts a mean weighted power to loudness in LUFS.
+fn loudness(power: f64) -> f32 {
+    (-0.691 + 10.0 * power.max(f64::MIN_POSITIVE).log10()) as f32
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// Returns how much a channel contributes to the loudness. The low frequency channel is ignored.
+fn channel_weight(speaker: Speaker) -> f64 {
+    match speaker {
+        Speaker::LowFrequency => 0.0,
+        Speaker::BackLeft | Speaker::BackRight | Speaker::SideLeft | Speaker::SideRight => 1.41,
+        _ => 1.0,
+    }
+}
+
+/// Creates the two stages of the K-weighting filter for [`SAMPLE_RATE`]: a high shelf modelling
+/// the acoustic effect of the head, followed by a high-pass.
+fn k_weighting() -> [Biquad; 2] {
+    let fs = SAMPLE_RATE as f64;
+
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
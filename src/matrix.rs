@@ -0,0 +1,202 @@
+//! Routing stereo output to the channels of a playback device.
+
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::{frames_mut, simd, ChannelLayout, Speaker};
+
+/// How the low frequency channel is treated by [`MixMatrix::downmix`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LfeMode {
+    /// Leave it out, as ITU-R BS.775 does.
+    #[default]
+    Discard,
+    /// Mix it into both the left and right channels at the given gain, in dB.
+    Mix(f32),
+}
+
+/// Routes the stereo output of an engine to the channels of a playback device, or folds the
+/// channels of the device back down to stereo.
+///
+/// The matrix holds, for each channel of the device, the gain the left and right channels of the
+/// engine are mixed into it at, or when downmixing, the gain it is mixed into the left and right
+/// channels at. Set the routing of an attached engine with
+/// [`Callback::set_routing`][crate::Callback::set_routing], and the downmix with
+/// [`Callback::set_downmix`][crate::Callback::set_downmix].
+/// # Example
+/// ```
+/// use rg3d_sound_sdl::{ChannelLayout, MixMatrix};
+///
+/// // Music to the front speakers, with a little of both sides in the center.
+/// let mut music = MixMatrix::front(ChannelLayout::Surround51);
+/// music.set(2, 0.3, 0.3);
+/// assert_eq!(music.gains(2), Some([0.3, 0.3]));
+/// assert_eq!(music.gains(4), Some([0.0, 0.0]));
+///
+/// // Sound effects to every speaker.
+/// let sfx = MixMatrix::spread(ChannelLayout::Surround51);
+/// assert_eq!(sfx.gains(4), Some([1.0, 0.0]));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixMatrix {
+    gains: Vec<[f32; 2]>,
+}
+
+impl MixMatrix {
+    /// Creates a matrix for the given number of output channels which routes nothing to any of
+    /// them.
+    pub fn silent(channels: usize) -> Self {
+        Self {
+            gains: vec![[0.0, 0.0]; channels],
+        }
+    }
+
+    /// Creates a matrix which plays the left and right channels through the front left and right
+    /// speakers of `layout`. On a mono device, both channels are mixed into the single speaker.
+    pub fn front(layout: ChannelLayout) -> Self {
+        Self {
+            gains: layout
+                .speakers()
+                .iter()
+                .map(|&speaker| match speaker {
+                    Speaker::FrontLeft | Speaker::FrontRight => speaker.spread_gains(),
+                    _ if layout == ChannelLayout::Mono => speaker.spread_gains(),
+                    _ => [0.0, 0.0],
+                })
+                .collect(),
+        }
+    }
+
+    /// Creates a matrix which spreads the left and right channels over every speaker of `layout`
+    /// on the same side, with both in the center, and nothing in the low frequency speaker.
+    pub fn spread(layout: ChannelLayout) -> Self {
+        Self {
+            gains: layout.speakers().iter().map(|s| s.spread_gains()).collect(),
+        }
+    }
+
+    /// Creates a matrix which spreads the left and right channels over the speakers of `layout`
+    /// like [`MixMatrix::spread`], turned down so that each carries the same power as it would
+    /// through a pair of stereo speakers, rather than getting louder the more speakers there are.
+    /// # Example
+    /// ```
+    /// use rg3d_sound_sdl::{ChannelLayout, MixMatrix};
+    ///
+    /// let quad = MixMatrix::upmix(ChannelLayout::Quad);
+    /// let [left, _] = quad.gains(0).unwrap();
+    /// assert!((left - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+    /// assert_eq!(MixMatrix::upmix(ChannelLayout::Stereo), MixMatrix::front(ChannelLayout::Stereo));
+    /// ```
+    pub fn upmix(layout: ChannelLayout) -> Self {
+        let mut matrix = Self::spread(layout);
+        for side in 0..2 {
+            let power: f32 = matrix.gains.iter().map(|g| g[side] * g[side]).sum();
+            if power > 0.0 {
+                let scale = power.sqrt().recip();
+                matrix.gains.iter_mut().for_each(|g| g[side] *= scale);
+            }
+        }
+        matrix
+    }
+
+    /// Creates a matrix which folds the channels of `layout` down to stereo with the ITU-R BS.775
+    /// coefficients: the front speakers at full level, the center at -3dB into both sides, and the
+    /// surround speakers at -3dB into their own side. The low frequency channel is left out or
+    /// mixed in according to `lfe`.
+    ///
+    /// Loud surround mixes can clip once folded down, which [`MixMatrix::normalize`] prevents.
+    /// # Example
+    /// ```
+    /// use rg3d_sound_sdl::{ChannelLayout, LfeMode, MixMatrix};
+    ///
+    /// let downmix = MixMatrix::downmix(ChannelLayout::Surround51, LfeMode::Discard);
+    /// assert_eq!(downmix.gains(0), Some([1.0, 0.0]));
+    /// assert_eq!(downmix.gains(3), Some([0.0, 0.0]));
+    /// let [left, right] = downmix.gains(2).unwrap();
+    /// assert!(left == right && (left - 0.7071).abs() < 1e-4);
+    ///
+    /// let downmix = MixMatrix::downmix(ChannelLayout::Surround51, LfeMode::Mix(0.0));
+    /// assert_eq!(downmix.gains(3), Some([1.0, 1.0]));
+    /// ```
+    pub fn downmix(layout: ChannelLayout, lfe: LfeMode) -> Self {
+        Self {
+            gains: layout
+                .speakers()
+                .iter()
+                .map(|speaker| match speaker {
+                    Speaker::FrontLeft => [1.0, 0.0],
+                    Speaker::FrontRight => [0.0, 1.0],
+                    Speaker::FrontCenter => [FRAC_1_SQRT_2, FRAC_1_SQRT_2],
+                    Speaker::LowFrequency => match lfe {
+                        LfeMode::Discard => [0.0, 0.0],
+                        LfeMode::Mix(db) => [10.0f32.powf(db / 20.0); 2],
+                    },
+                    Speaker::BackLeft | Speaker::SideLeft => [FRAC_1_SQRT_2, 0.0],
+                    Speaker::BackRight | Speaker::SideRight => [0.0, FRAC_1_SQRT_2],
+                })
+                .collect(),
+        }
+    }
+
+    /// Scales a downmix down, if necessary, so that neither the left nor the right channel can
+    /// clip when every channel is at full scale, as AC-3 decoders do. This keeps the balance of
+    /// the channels, at the cost of making the whole downmix quieter.
+    /// # Example
+    /// ```
+    /// use rg3d_sound_sdl::{ChannelLayout, LfeMode, MixMatrix};
+    ///
+    /// let mut downmix = MixMatrix::downmix(ChannelLayout::Quad, LfeMode::Discard);
+    /// downmix.normalize();
+    /// let [left, _] = downmix.gains(0).unwrap();
+    /// let [back_left, _] = downmix.gains(2).unwrap();
+    /// assert!((left + back_left - 1.0).abs() < 1e-6);
+    /// ```
+    pub fn normalize(&mut self) {
+        let sum = |side: usize| self.gains.iter().map(|g| g[side].abs()).sum::<f32>();
+        let peak = sum(0).max(sum(1));
+        if peak > 1.0 {
+            self.gains
+                .iter_mut()
+                .flatten()
+                .for_each(|gain| *gain /= peak);
+        }
+    }
+
+    /// Sets the gains the left and right channels are mixed into an output channel at.
+    /// # Panics
+    /// This function will panic if `channel` is not less than [`MixMatrix::channels`].
+    pub fn set(&mut self, channel: usize, left: f32, right: f32) {
+        self.gains[channel] = [left, right];
+    }
+
+    /// Returns the gains the left and right channels are mixed into an output channel at, or
+    /// `None` if the matrix has no such channel.
+    pub fn gains(&self, channel: usize) -> Option<[f32; 2]> {
+        self.gains.get(channel).copied()
+    }
+
+    /// Returns the number of output channels.
+    pub fn channels(&self) -> usize {
+        self.gains.len()
+    }
+
+    /// Mixes stereo frames into interleaved frames of output channels, scaled by `gain`.
+    pub(crate) fn mix_into(&self, from: &[(f32, f32)], to: &mut [f32], gain: f32) {
+        if self.gains == [[1.0, 0.0], [0.0, 1.0]] {
+            for (out, (left, right)) in frames_mut(to).iter_mut().zip(from) {
+                out[0] += left * gain;
+                out[1] += right * gain;
+            }
+            return;
+        }
+        for (frame, &(left, right)) in to.chunks_exact_mut(self.gains.len()).zip(from) {
+            for (out, [to_left, to_right]) in frame.iter_mut().zip(&self.gains) {
+                *out += (left * to_left + right * to_right) * gain;
+            }
+        }
+    }
+
+    /// Folds interleaved frames of output channels down to stereo, overwriting `to`.
+    pub(crate) fn fold_into(&self, from: &[f32], to: &mut [(f32, f32)]) {
+        simd::fold_to_stereo(from, &self.gains, to);
+    }
+}
@@ -0,0 +1,109 @@
+//! Metering the levels of individual engines attached to a [`Callback`][crate::Callback].
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+/// How much of the previous peak is kept after each block, so that a meter polled once per frame
+/// still shows short transients.
+const PEAK_RELEASE: f32 = 0.7;
+
+/// A meter of the peak and RMS levels of the left and right channels of an attached engine, for
+/// showing a level per bus in a mixer UI, for example one each for music, sound effects and
+/// voice contexts attached with [`Callback::attach_context`][crate::Callback::attach_context].
+///
+/// The levels are measured once per block, after the engine's gain and any ducking, and are
+/// linear, with 1 being full scale. The handle can be cloned and sent to any thread, and all
+/// clones refer to the same meter. Set the meter of an engine with
+/// [`Callback::set_meter`][crate::Callback::set_meter].
+/// # Example
+/// ```no_run
+/// use rg3d_sound::context::SoundContext;
+/// use rg3d_sound_sdl::LevelMeter;
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+///
+/// let music = LevelMeter::new();
+/// let mut callback = device.lock();
+/// let id = callback.attach_context(SoundContext::new(), 1.0);
+/// callback.set_meter(id, music.clone());
+/// drop(callback);
+/// device.resume();
+///
+/// // Once per frame:
+/// let [left, right] = music.peak();
+/// ```
+#[derive(Clone)]
+pub struct LevelMeter {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    /// The levels of the left and right channels as the bits of `f32`s.
+    peak: [AtomicU32; 2],
+    rms: [AtomicU32; 2],
+}
+
+impl LevelMeter {
+    /// Creates a meter which hasn't measured anything.
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                peak: [AtomicU32::new(0), AtomicU32::new(0)],
+                rms: [AtomicU32::new(0), AtomicU32::new(0)],
+            }),
+        }
+    }
+
+    /// Returns the peak levels of the left and right channels, which fall away gradually after a
+    /// transient.
+    pub fn peak(&self) -> [f32; 2] {
+        load(&self.shared.peak)
+    }
+
+    /// Returns the RMS levels of the left and right channels over the last block.
+    pub fn rms(&self) -> [f32; 2] {
+        load(&self.shared.rms)
+    }
+
+    /// Returns whether two handles refer to the same meter.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// Measures a block of stereo frames, updating the levels.
+    pub(crate) fn measure(&self, frames: &[(f32, f32)]) {
+        if frames.is_empty() {
+            return;
+        }
+        let (mut peak, mut sum) = ([0.0f32; 2], [0.0f32; 2]);
+        for &(left, right) in frames {
+            peak[0] = peak[0].max(left.abs());
+            peak[1] = peak[1].max(right.abs());
+            sum[0] += left * left;
+            sum[1] += right * right;
+        }
+        let held = self.peak();
+        for side in 0..2 {
+            let peak = peak[side].max(held[side] * PEAK_RELEASE);
+            let rms = (sum[side] / frames.len() as f32).sqrt();
+            self.shared.peak[side].store(peak.to_bits(), Ordering::Relaxed);
+            self.shared.rms[side].store(rms.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for LevelMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn load(levels: &[AtomicU32; 2]) -> [f32; 2] {
+    levels
+        .each_ref()
+        .map(|l| f32::from_bits(l.load(Ordering::Relaxed)))
+}
@@ -0,0 +1,148 @@
+//! Mirroring a single [`SoundEngine`] to several playback devices at once.
+
+use std::sync::{Arc, Mutex};
+
+use rg3d_sound::{context::SAMPLE_RATE, engine::SoundEngine};
+use sdl2::{audio::AudioDevice, AudioSubsystem};
+
+use crate::{
+    desired_spec, BlockRenderer, Callback, ChannelLayout, DeviceSelector, EngineHandle,
+    SpecMismatchPolicy,
+};
+
+/// The number of engine blocks kept in the broadcast ring. A device may fall this many blocks
+/// behind the fastest device before it starts skipping audio.
+const RING_BLOCKS: usize = 4;
+
+/// A device opened by [`open_multi`].
+pub type BroadcastDevice = AudioDevice<Callback<BroadcastReader>>;
+
+/// Opens several audio devices which all play the output of the same [`SoundEngine`].
+///
+/// The engine is rendered once into a shared ring of blocks, and every device reads its own copy
+/// of the output from that ring. The device that is furthest ahead drives rendering, so devices
+/// with different buffer timings can be mixed freely. A device whose clock runs slow enough to
+/// fall more than a few blocks behind skips ahead to the most recently rendered block.
+///
+/// On success, returns a handle to the engine and one [`AudioDevice`] per selector, in the same
+/// order as `devices`. On error, returns the SDL error of the first device that failed to open.
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::DeviceSelector;
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let (engine, devices) = rg3d_sound_sdl::open_multi(
+///     &audio,
+///     &[DeviceSelector::Default, DeviceSelector::Name("Virtual Sink".into())],
+/// )
+/// .unwrap();
+/// for device in &devices {
+///     device.resume();
+/// }
+/// ```
+pub fn open_multi(
+    subsystem: &AudioSubsystem,
+    devices: &[DeviceSelector],
+) -> Result<(EngineHandle, Vec<BroadcastDevice>), String> {
+    let desired = desired_spec();
+    let engine = crate::new_engine();
+    let broadcast = Arc::new(Mutex::new(Broadcast::new(Arc::clone(&engine))));
+
+    let devices = devices
+        .iter()
+        .map(|selector| {
+            let name = selector.resolve(None)?;
+            let mut mismatch = Ok(());
+            let device = subsystem.open_playback(name.as_deref(), &desired, |obtained| {
+                mismatch = SpecMismatchPolicy::default().check(
+                    &obtained,
+                    SAMPLE_RATE as i32,
+                    ChannelLayout::Stereo,
+                );
+                Callback::new(BroadcastReader::new(Arc::clone(&broadcast)))
+            })?;
+            mismatch.map(|_| device)
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((engine, devices))
+}
+
+/// The shared state of every [`BroadcastReader`] reading from the same engine.
+struct Broadcast {
+    engine: EngineHandle,
+    ring: Vec<(f32, f32)>,
+    /// The total number of frames rendered into the ring so far.
+    written: u64,
+}
+
+impl Broadcast {
+    fn new(engine: EngineHandle) -> Self {
+        Self {
+            engine,
+            ring: vec![(0.0, 0.0); RING_BLOCKS * SoundEngine::render_buffer_len()],
+            written: 0,
+        }
+    }
+
+    /// Renders the next block from the engine into the ring, overwriting the oldest block.
+    fn render_block(&mut self) {
+        let block_len = SoundEngine::render_buffer_len();
+        let start = (self.written % self.ring.len() as u64) as usize;
+        let block = &mut self.ring[start..start + block_len];
+        self.engine.lock().unwrap().render(block);
+        self.written += block_len as u64;
+    }
+
+    /// Copies the frames starting at `cursor` into `buf`, rendering more blocks as needed, and
+    /// returns the new cursor position.
+    fn read(&mut self, mut cursor: u64, buf: &mut [(f32, f32)]) -> u64 {
+        let block_len = SoundEngine::render_buffer_len() as u64;
+        let mut filled = 0;
+        while filled < buf.len() {
+            if cursor >= self.written {
+                self.render_block();
+            }
+            if self.written - cursor > self.ring.len() as u64 {
+                // This reader fell too far behind and its frames were overwritten.
+                cursor = self.written - block_len;
+            }
+
+            let start = (cursor % self.ring.len() as u64) as usize;
+            let available = (self.written - cursor) as usize;
+            let len = available
+                .min(self.ring.len() - start)
+                .min(buf.len() - filled);
+            buf[filled..filled + len].copy_from_slice(&self.ring[start..start + len]);
+            filled += len;
+            cursor += len as u64;
+        }
+        cursor
+    }
+}
+
+/// A [`BlockRenderer`] which plays a copy of the output of a [`SoundEngine`] shared with other
+/// devices. Used by the devices opened with [`open_multi`].
+pub struct BroadcastReader {
+    broadcast: Arc<Mutex<Broadcast>>,
+    /// The position of this device in the shared stream of rendered frames.
+    cursor: Option<u64>,
+}
+
+impl BroadcastReader {
+    fn new(broadcast: Arc<Mutex<Broadcast>>) -> Self {
+        Self {
+            broadcast,
+            cursor: None,
+        }
+    }
+}
+
+impl BlockRenderer for BroadcastReader {
+    fn render(&mut self, buf: &mut [(f32, f32)]) {
+        let mut broadcast = self.broadcast.lock().unwrap();
+        // Devices join the stream at whatever point it has reached when they first play.
+        let cursor = self.cursor.unwrap_or(broadcast.written);
+        self.cursor = Some(broadcast.read(cursor, buf));
+    }
+}
@@ -0,0 +1,177 @@
+//! Streaming the output of a [`Callback`][crate::Callback] over the network.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{Arc, Mutex, Weak},
+    thread,
+    time::Duration,
+};
+
+use rg3d_sound::context::SAMPLE_RATE;
+
+use crate::simd;
+
+/// How often the sending thread wakes up to send whatever has been written.
+const INTERVAL: Duration = Duration::from_millis(10);
+/// How long a client may block the sending thread before it is disconnected.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(250);
+/// The most frames sent in one UDP datagram, keeping datagrams under a typical MTU.
+const DATAGRAM_FRAMES: usize = 360;
+
+/// Where a [`NetworkSink`] sends the audio.
+enum Transport {
+    /// Accepts any number of clients, sending each a WAV header followed by the audio.
+    Tcp {
+        listener: TcpListener,
+        clients: Vec<TcpStream>,
+    },
+    /// Sends raw samples to a single address.
+    Udp { socket: UdpSocket },
+}
+
+/// A tap on the final mixed output of a [`Callback`][crate::Callback] which streams it over the
+/// network as 16-bit stereo PCM at [`SAMPLE_RATE`], so that a companion app or a remote debugging
+/// session can hear exactly what the game plays.
+///
+/// The callback only copies its output into a short buffer, and a thread of the sink's own does
+/// the encoding and sending, so a slow network never holds up the audio. If the network can't
+/// keep up, the oldest audio is dropped. The thread stops once every handle to the sink, including
+/// the one given to the callback, has been dropped.
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::NetworkSink;
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+///
+/// // Listen with, for example, `ffplay tcp://localhost:7777`.
+/// let sink = NetworkSink::listen_tcp("0.0.0.0:7777").unwrap();
+/// device.lock().add_network_sink(sink);
+/// device.resume();
+/// ```
+#[derive(Clone)]
+pub struct NetworkSink {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    ring: Mutex<VecDeque<(f32, f32)>>,
+    capacity: usize,
+}
+
+impl NetworkSink {
+    /// Creates a sink which listens for TCP connections on the given address. Each client is sent
+    /// a WAV header of unknown length, followed by the audio from the moment it connected. On
+    /// error, returns the error binding the address.
+    pub fn listen_tcp(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self::spawn(Transport::Tcp {
+            listener,
+            clients: Vec::new(),
+        }))
+    }
+
+    /// Creates a sink which sends datagrams of raw little-endian samples to the given address,
+    /// from a socket bound to any local port. On error, returns the error creating the socket.
+    pub fn send_udp(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let target = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No address to send to"))?;
+        let local: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(target)?;
+        Ok(Self::spawn(Transport::Udp { socket }))
+    }
+
+    /// Returns whether two handles refer to the same sink.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    fn spawn(mut transport: Transport) -> Self {
+        let shared = Arc::new(Shared {
+            ring: Mutex::new(VecDeque::new()),
+            capacity: SAMPLE_RATE as usize,
+        });
+        let weak = Arc::downgrade(&shared);
+        thread::Builder::new()
+            .name("rg3d-sound-sdl network sink".into())
+            .spawn(move || run(weak, &mut transport))
+            .expect("Failed to spawn network sink thread");
+        Self { shared }
+    }
+
+    /// Copies output frames to the buffer, without waiting if the sending thread is using it.
+    pub(crate) fn write(&self, buf: &[(f32, f32)]) {
+        if let Ok(mut ring) = self.shared.ring.try_lock() {
+            ring.extend(buf);
+            let excess = ring.len().saturating_sub(self.shared.capacity);
+            ring.drain(..excess);
+        }
+    }
+}
+
+/// Sends whatever has been written to the sink, until every handle to it has been dropped.
+fn run(shared: Weak<Shared>, transport: &mut Transport) {
+    let (mut samples, mut converted, mut bytes) = (Vec::new(), Vec::new(), Vec::new());
+    while let Some(shared) = shared.upgrade() {
+        samples.clear();
+        let frames = shared.ring.lock().unwrap().drain(..).collect::<Vec<_>>();
+        drop(shared);
+        samples.extend(frames.into_iter().flat_map(|(left, right)| [left, right]));
+
+        converted.resize(samples.len(), 0);
+        simd::f32_to_i16(&samples, &mut converted);
+        bytes.clear();
+        bytes.extend(converted.iter().flat_map(|sample| sample.to_le_bytes()));
+        match transport {
+            Transport::Tcp { listener, clients } => {
+                while let Ok((mut client, _)) = listener.accept() {
+                    let accepted = client
+                        .set_nonblocking(false)
+                        .and_then(|_| client.set_write_timeout(Some(WRITE_TIMEOUT)))
+                        .and_then(|_| client.write_all(&wav_header()));
+                    if accepted.is_ok() {
+                        clients.push(client);
+                    }
+                }
+                clients.retain_mut(|client| client.write_all(&bytes).is_ok());
+            }
+            Transport::Udp { socket } => {
+                for datagram in bytes.chunks(DATAGRAM_FRAMES * 4) {
+                    // Nobody listening isn't an error worth stopping for.
+                    let _ = socket.send(datagram);
+                }
+            }
+        }
+        thread::sleep(INTERVAL);
+    }
+}
+
+/// Returns the header of a 16-bit stereo WAV stream of unknown length.
+fn wav_header() -> Vec<u8> {
+    let rate = SAMPLE_RATE;
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    header.extend_from_slice(&2u16.to_le_bytes()); // Channels
+    header.extend_from_slice(&rate.to_le_bytes());
+    header.extend_from_slice(&(rate * 4).to_le_bytes()); // Bytes per second
+    header.extend_from_slice(&4u16.to_le_bytes()); // Bytes per frame
+    header.extend_from_slice(&16u16.to_le_bytes()); // Bits per sample
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header
+}
@@ -0,0 +1,45 @@
+//! Keeping a [`Callback`] running while its device is lost.
+
+use std::time::{Duration, Instant};
+
+use sdl2::audio::AudioCallback;
+
+use crate::{Callback, ObtainedSpec};
+
+/// The most audio rendered at once to catch up, so that a long hitch in the game doesn't stall it
+/// further rendering audio nobody will hear.
+const MAX_CATCH_UP: Duration = Duration::from_secs(1);
+
+/// Runs a callback at the rate its device would, throwing away what it renders, so that sources
+/// keep playing in time while there's no device to hear them through.
+pub(crate) struct NullClock {
+    last: Instant,
+    /// The number of frames which should have been rendered but haven't been yet.
+    owed: f64,
+    buf: Vec<f32>,
+}
+
+impl NullClock {
+    pub(crate) fn new() -> Self {
+        Self {
+            last: Instant::now(),
+            owed: 0.0,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Runs the callback a buffer of the obtained size at a time, as SDL would, for as long as
+    /// has passed since the last tick.
+    pub(crate) fn tick(&mut self, callback: &mut Callback, spec: ObtainedSpec) {
+        let now = Instant::now();
+        let elapsed = (now - self.last).min(MAX_CATCH_UP);
+        self.last = now;
+        self.owed += elapsed.as_secs_f64() * f64::from(spec.freq);
+        let frames = usize::from(spec.samples);
+        self.buf.resize(frames * usize::from(spec.channels), 0.0);
+        while self.owed >= frames as f64 {
+            callback.callback(&mut self.buf);
+            self.owed -= frames as f64;
+        }
+    }
+}
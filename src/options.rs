@@ -0,0 +1,570 @@
+//! Options for opening a playback device.
+
+use std::{
+    ffi::CString, mem::MaybeUninit, os::raw::c_int, path::PathBuf, ptr, sync::Arc, time::Duration,
+};
+
+use rg3d_sound::{context::SAMPLE_RATE, engine::SoundEngine};
+use sdl2::{
+    audio::{AudioDevice, AudioFormat, AudioSpec, AudioSpecDesired},
+    sys, AudioSubsystem,
+};
+
+use crate::{
+    desired_spec,
+    event::{self, EventSender},
+    priority, Callback, ChannelLayout, DeviceSelector, EngineHandle, FallbackError,
+    RateChangePolicy, ReconnectPolicy, SdlSound, UnderrunFill, WatchdogPolicy, DEFAULT_GAIN_RAMP,
+};
+
+/// What to do when the spec SDL obtains for a device differs from the one this crate asks for.
+///
+/// Unless [`OpenOptions::allowed_changes`] lets the device open with the hardware's own sample
+/// rate or channel count, SDL converts the audio to whatever the hardware needs. This policy
+/// controls how the remaining difference, the size of the device's buffer, is negotiated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpecMismatchPolicy {
+    /// Fail to open the device, returning an error describing the difference.
+    Fail,
+    /// Ask for buffers the same size as the engine renders, but if SDL obtains a different size,
+    /// split or combine rendered blocks to fill the buffers it asks for.
+    #[default]
+    ConvertInternally,
+    /// Don't ask for any particular buffer size, and accept whatever the driver prefers. Blocks
+    /// are converted to that size as with [`SpecMismatchPolicy::ConvertInternally`], but SDL won't
+    /// need to rebuffer the audio itself.
+    AcceptObtained,
+}
+
+impl SpecMismatchPolicy {
+    /// Checks that this crate can play audio at the given sample rate and channel layout to a
+    /// device with the obtained spec under this policy. On error, returns a description of the
+    /// mismatch.
+    pub(crate) fn check(
+        self,
+        obtained: &AudioSpec,
+        freq: i32,
+        layout: ChannelLayout,
+    ) -> Result<(), String> {
+        if obtained.freq != freq {
+            return Err(format!("Invalid sample rate: {}", obtained.freq));
+        }
+        if obtained.channels != layout.channels() {
+            return Err(format!("Invalid number of channels: {}", obtained.channels));
+        }
+        if obtained.format != AudioFormat::f32_sys() {
+            return Err(format!("Invalid sample format: {:?}", obtained.format));
+        }
+        if self == Self::Fail && obtained.samples as usize != SoundEngine::render_buffer_len() {
+            return Err(format!("Invalid buffer size: {}", obtained.samples));
+        }
+        Ok(())
+    }
+}
+
+/// Which of SDL's allowed changes to open a device with, set with
+/// [`OpenOptions::allowed_changes`]. Each change allowed lets the driver open the device as the
+/// hardware is, rather than SDL converting the audio to suit it.
+///
+/// The sample format can't be changed, as the engine always renders 32-bit floats, and the
+/// buffer size is left to [`SpecMismatchPolicy::AcceptObtained`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllowedChanges {
+    /// Allows the sample rate to change, as with `SDL_AUDIO_ALLOW_FREQUENCY_CHANGE`. Everything
+    /// is still mixed and processed at [`SAMPLE_RATE`], and the finished output is resampled to
+    /// the device's rate, as with [`RateChangePolicy::Resample`].
+    pub frequency: bool,
+    /// Allows the channel count to change, as with `SDL_AUDIO_ALLOW_CHANNELS_CHANGE`, if the
+    /// device has as many channels as one of the [`ChannelLayout`]s, which is then used instead
+    /// of the one set with [`OpenOptions::channel_layout`]. SDL still converts to any other count.
+    pub channels: bool,
+}
+
+impl AllowedChanges {
+    /// Returns the `SDL_AUDIO_ALLOW_*` flags for these changes.
+    fn flags(self) -> c_int {
+        let mut flags = 0;
+        if self.frequency {
+            flags |= sys::SDL_AUDIO_ALLOW_FREQUENCY_CHANGE;
+        }
+        if self.channels {
+            flags |= sys::SDL_AUDIO_ALLOW_CHANNELS_CHANGE;
+        }
+        flags as c_int
+    }
+
+    /// Finds the sample rate and channel count the named device, or the default device for
+    /// `None`, would be opened with under these changes. The `sdl2` crate always opens devices
+    /// with no changes allowed, so the device is briefly opened through SDL itself to find out.
+    fn probe(self, name: Option<&str>, desired: &AudioSpecDesired) -> Result<(i32, u8), String> {
+        let name = name
+            .map(CString::new)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let desired = sys::SDL_AudioSpec {
+            freq: desired.freq.unwrap_or(SAMPLE_RATE as i32),
+            format: sys::AUDIO_F32SYS as sys::SDL_AudioFormat,
+            channels: desired.channels.unwrap_or(2),
+            silence: 0,
+            samples: desired.samples.unwrap_or(0),
+            padding: 0,
+            size: 0,
+            callback: None,
+            userdata: ptr::null_mut(),
+        };
+        let mut obtained = MaybeUninit::uninit();
+        // SAFETY: The name is null or a valid C string, and both specs outlive the call. Without
+        // a callback, the device is opened to be queued to, which it never is before it's closed.
+        unsafe {
+            let id = sys::SDL_OpenAudioDevice(
+                name.as_ref().map_or(ptr::null(), |name| name.as_ptr()),
+                0,
+                &desired,
+                obtained.as_mut_ptr(),
+                self.flags(),
+            );
+            if id == 0 {
+                return Err(sdl2::get_error());
+            }
+            sys::SDL_CloseAudioDevice(id);
+            let obtained: sys::SDL_AudioSpec = obtained.assume_init();
+            Ok((obtained.freq, obtained.channels))
+        }
+    }
+}
+
+/// The device [`OpenOptions::prepare`] found to open, and the spec to ask for.
+struct Prepared {
+    name: Option<String>,
+    desired: AudioSpecDesired,
+    layout: ChannelLayout,
+}
+
+/// Options controlling how a playback device is opened, in the style of
+/// [`std::fs::OpenOptions`].
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::{DeviceSelector, OpenOptions};
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let sound = OpenOptions::new()
+///     .device(DeviceSelector::Index(1))
+///     .start_paused(false)
+///     .open(&audio)
+///     .unwrap();
+/// assert!(!sound.is_paused());
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    pub(crate) device: DeviceSelector,
+    pub(crate) start_paused: bool,
+    pub(crate) spec_mismatch: SpecMismatchPolicy,
+    pub(crate) channel_layout: ChannelLayout,
+    pub(crate) reconnect: Option<ReconnectPolicy>,
+    pub(crate) driver: Option<String>,
+    pub(crate) realtime: bool,
+    pub(crate) watchdog: Option<WatchdogPolicy>,
+    pub(crate) rate_change: Option<RateChangePolicy>,
+    pub(crate) null_fallback: bool,
+    pub(crate) underrun_fill: UnderrunFill,
+    pub(crate) gain_ramp: Duration,
+    pub(crate) allowed_changes: AllowedChanges,
+}
+
+impl OpenOptions {
+    /// Creates the default options: open the default device, and leave it paused.
+    pub fn new() -> Self {
+        Self {
+            device: DeviceSelector::Default,
+            start_paused: true,
+            spec_mismatch: SpecMismatchPolicy::default(),
+            channel_layout: ChannelLayout::default(),
+            reconnect: None,
+            driver: None,
+            realtime: false,
+            watchdog: None,
+            rate_change: None,
+            null_fallback: false,
+            underrun_fill: UnderrunFill::default(),
+            gain_ramp: DEFAULT_GAIN_RAMP,
+            allowed_changes: AllowedChanges::default(),
+        }
+    }
+
+    /// Sets which device to open. Defaults to [`DeviceSelector::Default`].
+    pub fn device(&mut self, device: DeviceSelector) -> &mut Self {
+        self.device = device;
+        self
+    }
+
+    /// Sets whether the device is left paused once opened, like SDL does, or starts playing
+    /// straight away. Defaults to `true`, so [`SdlSound::resume`] must be called before anything
+    /// is heard.
+    pub fn start_paused(&mut self, start_paused: bool) -> &mut Self {
+        self.start_paused = start_paused;
+        self
+    }
+
+    /// Sets what happens when the spec SDL obtains for the device differs from the one asked for.
+    /// Defaults to [`SpecMismatchPolicy::ConvertInternally`].
+    pub fn spec_mismatch(&mut self, policy: SpecMismatchPolicy) -> &mut Self {
+        self.spec_mismatch = policy;
+        self
+    }
+
+    /// Sets which of the device's sample rate and channel count may differ from those asked for,
+    /// rather than SDL converting the audio to them. Defaults to allowing neither.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::{AllowedChanges, OpenOptions};
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let sound = OpenOptions::new()
+    ///     .allowed_changes(AllowedChanges {
+    ///         frequency: true,
+    ///         channels: true,
+    ///     })
+    ///     .open(&audio)
+    ///     .unwrap();
+    /// println!("Playing at {} Hz", sound.obtained_spec().freq);
+    /// ```
+    pub fn allowed_changes(&mut self, changes: AllowedChanges) -> &mut Self {
+        self.allowed_changes = changes;
+        self
+    }
+
+    /// Sets the channel layout to open the device with. SDL converts it to whatever the hardware
+    /// has if necessary, unless [`AllowedChanges::channels`] is set. Defaults to
+    /// [`ChannelLayout::Stereo`].
+    pub fn channel_layout(&mut self, layout: ChannelLayout) -> &mut Self {
+        self.channel_layout = layout;
+        self
+    }
+
+    /// Sets whether and how often to try reopening the device if it is lost, while
+    /// [`SdlSound::update`] is being called. Defaults to `None`, which leaves a lost device silent.
+    pub fn reconnect(&mut self, policy: impl Into<Option<ReconnectPolicy>>) -> &mut Self {
+        self.reconnect = policy.into();
+        self
+    }
+
+    /// Sets the SDL audio driver to open the device with, such as `"pulseaudio"` or `"wasapi"`.
+    /// Switching drivers closes every open audio device, so opening fails while any are, except
+    /// that [`SdlSound::reopen`] and [`SdlSound::switch_device`] close their own device first.
+    /// Defaults to whichever driver is already in use, or the one in use before switching to the
+    /// dummy or disk driver.
+    pub fn driver(&mut self, driver: impl Into<String>) -> &mut Self {
+        self.driver = Some(driver.into());
+        self
+    }
+
+    /// Sets whether to give the device's audio thread real-time priority, so that it isn't
+    /// starved by the game's own threads under load. SDL is asked to do this where it can, and
+    /// with the `realtime` feature on Unix, the callback also tries to from the audio thread. If
+    /// the process isn't allowed real-time scheduling, the thread keeps its normal priority.
+    /// Defaults to `false`.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::OpenOptions;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = OpenOptions::new()
+    ///     .realtime_priority(true)
+    ///     .start_paused(false)
+    ///     .open(&audio)
+    ///     .unwrap();
+    /// std::thread::sleep(std::time::Duration::from_millis(200));
+    /// println!("Real-time: {}", sound.device_mut().lock().is_realtime());
+    /// ```
+    pub fn realtime_priority(&mut self, realtime: bool) -> &mut Self {
+        self.realtime = realtime;
+        self
+    }
+
+    /// Sets whether and how to watch for the driver no longer asking the device for audio. Defaults
+    /// to `None`, which doesn't watch.
+    pub fn watchdog(&mut self, policy: impl Into<Option<WatchdogPolicy>>) -> &mut Self {
+        self.watchdog = policy.into();
+        self
+    }
+
+    /// Sets whether and how to follow the driver changing the sample rate of the device while it
+    /// plays. Defaults to `None`, which doesn't watch for changes.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::{OpenOptions, RateChangePolicy};
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = OpenOptions::new()
+    ///     .rate_change(RateChangePolicy::Resample)
+    ///     .start_paused(false)
+    ///     .open(&audio)
+    ///     .unwrap();
+    ///
+    /// // In the game loop:
+    /// sound.update();
+    /// ```
+    pub fn rate_change(&mut self, policy: impl Into<Option<RateChangePolicy>>) -> &mut Self {
+        self.rate_change = policy.into();
+        self
+    }
+
+    /// Sets whether the engines keep running in real time, silently, while the device is lost,
+    /// for example because the only device was unplugged, so that sources carry on playing rather
+    /// than freezing until a device is back. The engines are run from [`SdlSound::update`], so it
+    /// must keep being called. Combine this with [`OpenOptions::reconnect`] to switch back to a
+    /// real device once one is available. Defaults to `false`.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::{OpenOptions, ReconnectPolicy};
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = OpenOptions::new()
+    ///     .reconnect(ReconnectPolicy::default())
+    ///     .null_fallback(true)
+    ///     .start_paused(false)
+    ///     .open(&audio)
+    ///     .unwrap();
+    ///
+    /// // In the game loop:
+    /// sound.update();
+    /// ```
+    pub fn null_fallback(&mut self, enabled: bool) -> &mut Self {
+        self.null_fallback = enabled;
+        self
+    }
+
+    /// Sets what is played where audio is missing, as with [`Callback::set_underrun_fill`].
+    /// Defaults to [`UnderrunFill::Silence`].
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use rg3d_sound_sdl::{OpenOptions, UnderrunFill};
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let sound = OpenOptions::new()
+    ///     .underrun_fill(UnderrunFill::Fade(Duration::from_millis(5)))
+    ///     .open(&audio)
+    ///     .unwrap();
+    /// ```
+    pub fn underrun_fill(&mut self, fill: UnderrunFill) -> &mut Self {
+        self.underrun_fill = fill.clamped();
+        self
+    }
+
+    /// Sets how long gain changes take to ramp, as with [`Callback::set_gain_ramp`]. Defaults to
+    /// [`DEFAULT_GAIN_RAMP`].
+    pub fn gain_ramp(&mut self, ramp: Duration) -> &mut Self {
+        self.gain_ramp = ramp;
+        self
+    }
+
+    /// Opens a playback device with these options, driven by a new [`SoundEngine`]. On error,
+    /// returns the SDL error, or a description of why the obtained spec was rejected.
+    pub fn open(&self, subsystem: &AudioSubsystem) -> Result<SdlSound, String> {
+        self.open_sound(subsystem, self.prepare()?)
+    }
+
+    /// Opens the device found by [`OpenOptions::prepare`] as an [`SdlSound`].
+    fn open_sound(
+        &self,
+        subsystem: &AudioSubsystem,
+        prepared: Prepared,
+    ) -> Result<SdlSound, String> {
+        let engine = crate::new_engine();
+        let events = event::channel();
+        let (device, name) = self.open_prepared(subsystem, &engine, &events.0, prepared)?;
+        let sound = SdlSound::new(
+            subsystem.clone(),
+            self.clone(),
+            engine,
+            device,
+            name,
+            events,
+        );
+        if !self.start_paused {
+            sound.resume();
+        }
+        Ok(sound)
+    }
+
+    /// Opens the device of SDL's dummy driver with these options, which plays nothing but asks
+    /// for audio at the rate a real device would, so that tests and CI can run the whole playback
+    /// path without audio hardware. See [`DeviceSelector::Dummy`].
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::OpenOptions;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let sound = OpenOptions::new().start_paused(false).open_dummy(&audio).unwrap();
+    /// assert_eq!(audio.current_audio_driver(), "dummy");
+    /// ```
+    pub fn open_dummy(&self, subsystem: &AudioSubsystem) -> Result<SdlSound, String> {
+        self.clone().device(DeviceSelector::Dummy).open(subsystem)
+    }
+
+    /// Opens the device of SDL's disk driver with these options, which writes everything played
+    /// to the file at `path` as raw samples in the format of the obtained spec, for rendering
+    /// audio offline. See [`DeviceSelector::Disk`].
+    /// # Example
+    /// ```no_run
+    /// use std::{thread, time::Duration};
+    ///
+    /// use rg3d_sound_sdl::OpenOptions;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let sound = OpenOptions::new()
+    ///     .start_paused(false)
+    ///     .open_disk(&audio, "out.raw")
+    ///     .unwrap();
+    /// let spec = sound.obtained_spec();
+    ///
+    /// thread::sleep(Duration::from_secs(1));
+    /// drop(sound);
+    /// // out.raw now holds about a second of audio, as `spec.channels` interleaved channels of
+    /// // `spec.format` samples at `spec.freq`.
+    /// ```
+    pub fn open_disk(
+        &self,
+        subsystem: &AudioSubsystem,
+        path: impl Into<PathBuf>,
+    ) -> Result<SdlSound, String> {
+        self.clone()
+            .device(DeviceSelector::Disk(path.into()))
+            .open(subsystem)
+    }
+
+    /// Like [`OpenOptions::open`], but does the slow part of opening the device on a thread of its
+    /// own, so that the executor isn't blocked: switching drivers, enumerating devices and probing
+    /// the device for [`OpenOptions::allowed_changes`]. If the future is dropped before that's
+    /// done, dropping it waits for it to finish.
+    ///
+    /// SDL's audio handles can't be moved between threads, so the device itself is then opened on
+    /// the thread polling the future, and isn't `Send`. Drive the future on the thread which will
+    /// use the device, for example with a local executor.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::OpenOptions;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let options = OpenOptions::new();
+    /// let sound = futures::executor::block_on(options.open_async(&audio)).unwrap();
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn open_async(&self, subsystem: &AudioSubsystem) -> Result<SdlSound, String> {
+        let options = self.clone();
+        let prepared = crate::asynchronous::spawn_blocking(move || options.prepare()).await?;
+        self.open_sound(subsystem, prepared)
+    }
+
+    /// Opens a paused playback device with these options, whose callback renders `engine` and
+    /// sends its events to `events`. Returns the device and its name, or `None` for the default
+    /// device.
+    pub(crate) fn open_device(
+        &self,
+        subsystem: &AudioSubsystem,
+        engine: &EngineHandle,
+        events: &EventSender,
+    ) -> Result<(AudioDevice<Callback>, Option<String>), String> {
+        let prepared = self.prepare()?;
+        self.open_prepared(subsystem, engine, events, prepared)
+    }
+
+    /// Does everything opening a device with these options needs before the device itself is
+    /// opened: switches drivers if the device needs another, resolves the selector to a device
+    /// name, and probes the device if any changes are allowed. This only uses SDL functions which
+    /// can be called from any thread while the audio subsystem is initialized, so that it can be
+    /// done on another thread.
+    fn prepare(&self) -> Result<Prepared, String> {
+        let name = self.device.resolve(self.driver.as_deref())?;
+
+        let mut desired = desired_spec();
+        if self.spec_mismatch == SpecMismatchPolicy::AcceptObtained {
+            desired.samples = None;
+        }
+        desired.channels = Some(self.channel_layout.channels());
+        let mut layout = self.channel_layout;
+        if self.allowed_changes != AllowedChanges::default() {
+            let (freq, channels) = self.allowed_changes.probe(name.as_deref(), &desired)?;
+            desired.freq = Some(freq);
+            if let Some(obtained) = ChannelLayout::from_channels(channels) {
+                layout = obtained;
+                desired.channels = Some(channels);
+            }
+        }
+        Ok(Prepared {
+            name,
+            desired,
+            layout,
+        })
+    }
+
+    /// Opens the device found by [`OpenOptions::prepare`], as [`OpenOptions::open_device`] does.
+    fn open_prepared(
+        &self,
+        subsystem: &AudioSubsystem,
+        engine: &EngineHandle,
+        events: &EventSender,
+        prepared: Prepared,
+    ) -> Result<(AudioDevice<Callback>, Option<String>), String> {
+        let Prepared {
+            name,
+            desired,
+            layout,
+        } = prepared;
+        if self.realtime {
+            priority::set_hint();
+        }
+
+        let mut mismatch = Ok(());
+        let device = subsystem.open_playback(name.as_deref(), &desired, |obtained| {
+            let freq = desired.freq.unwrap_or(SAMPLE_RATE as i32);
+            mismatch = self.spec_mismatch.check(&obtained, freq, layout);
+            let mut callback = Callback::new(Arc::clone(engine));
+            callback.set_channel_layout(layout);
+            callback.set_device_rate(freq as u32);
+            callback.set_events(events.clone());
+            callback.request_realtime(self.realtime);
+            callback.set_underrun_fill(self.underrun_fill);
+            callback.set_gain_ramp(self.gain_ramp);
+            callback
+        })?;
+        mismatch.map(|_| (device, name))
+    }
+
+    /// Tries to open each of the given devices in order with these options, ignoring the device
+    /// set with [`OpenOptions::device`], and returns the first which succeeds. On error, returns
+    /// the error each device failed with.
+    pub fn open_with_fallbacks(
+        &self,
+        subsystem: &AudioSubsystem,
+        devices: &[DeviceSelector],
+    ) -> Result<SdlSound, FallbackError> {
+        let mut errors = Vec::new();
+        for device in devices {
+            match self.clone().device(device.clone()).open(subsystem) {
+                Ok(sound) => return Ok(sound),
+                Err(e) => errors.push((device.clone(), e)),
+            }
+        }
+        Err(FallbackError { errors })
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,25 @@
+//! The types most users of this crate need, along with the `rg3d_sound` items needed to play a
+//! sound, for importing with `use rg3d_sound_sdl::prelude::*`.
+//! # Example
+//! ```no_run
+//! use rg3d_sound_sdl::prelude::*;
+//!
+//! let sdl = sdl2::init().unwrap();
+//! let audio = sdl.audio().unwrap();
+//! let sound = OpenOptions::new().start_paused(false).open(&audio).unwrap();
+//!
+//! let ctx = SoundContext::new();
+//! sound.engine().lock().unwrap().add_context(ctx.clone());
+//! ```
+
+pub use rg3d_sound::{
+    buffer::{DataSource, SoundBufferResource},
+    context::SoundContext,
+    engine::SoundEngine,
+    source::{generic::GenericSourceBuilder, Status},
+};
+
+pub use crate::{
+    AudioConfig, AudioEvent, Callback, ChannelLayout, DeviceSelector, EngineHandle, EngineId,
+    ExternalInput, ObtainedSpec, OpenOptions, ReconnectPolicy, SdlSound, UnderrunFill,
+};
@@ -0,0 +1,46 @@
+//! Raising the priority of SDL's audio thread.
+
+/// The SDL hint making SDL give its audio thread real-time scheduling, on platforms where it
+/// supports that.
+const FORCE_REALTIME_HINT: &str = "SDL_THREAD_FORCE_REALTIME_TIME_CRITICAL";
+
+/// Asks SDL to give the threads of devices opened from now on real-time priority. SDL falls back
+/// to its usual priority if this isn't allowed.
+pub(crate) fn set_hint() {
+    sdl2::hint::set(FORCE_REALTIME_HINT, "1");
+}
+
+/// Tries to give the current thread real-time priority, returning whether it has it. Without the
+/// `realtime` feature, or on platforms other than Unix, this only reports `false`.
+#[cfg(all(unix, feature = "realtime"))]
+pub(crate) fn elevate_current_thread() -> bool {
+    // SAFETY: These only read and change the scheduling of the calling thread, and `param` is a
+    // valid, initialized `sched_param`.
+    unsafe {
+        let thread = libc::pthread_self();
+        let mut policy = 0;
+        let mut param: libc::sched_param = std::mem::zeroed();
+        if libc::pthread_getschedparam(thread, &mut policy, &mut param) == 0
+            && (policy == libc::SCHED_FIFO || policy == libc::SCHED_RR)
+        {
+            // SDL already managed it.
+            return true;
+        }
+        let min = libc::sched_get_priority_min(libc::SCHED_FIFO);
+        let max = libc::sched_get_priority_max(libc::SCHED_FIFO);
+        if min < 0 || max < 0 {
+            return false;
+        }
+        // High enough to beat ordinary real-time threads, while leaving room above for the
+        // system's own.
+        param.sched_priority = min + (max - min) * 3 / 4;
+        libc::pthread_setschedparam(thread, libc::SCHED_FIFO, &param) == 0
+    }
+}
+
+/// Tries to give the current thread real-time priority, returning whether it has it. Without the
+/// `realtime` feature, or on platforms other than Unix, this only reports `false`.
+#[cfg(not(all(unix, feature = "realtime")))]
+pub(crate) fn elevate_current_thread() -> bool {
+    false
+}
@@ -0,0 +1,104 @@
+//! Smoothing changes to gains, so that they don't click.
+
+use std::time::Duration;
+
+use rg3d_sound::context::SAMPLE_RATE;
+
+use crate::simd;
+
+/// A gain which moves linearly to each new target over a number of frames, rather than jumping
+/// to it, which would click.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Ramp {
+    current: f32,
+    target: f32,
+    /// The change in gain each frame, while ramping.
+    step: f32,
+    /// The number of frames left until the gain reaches its target.
+    left: usize,
+}
+
+impl Ramp {
+    pub(crate) fn new(gain: f32) -> Self {
+        Self {
+            current: gain,
+            target: gain,
+            step: 0.0,
+            left: 0,
+        }
+    }
+
+    /// Returns the gain being ramped to.
+    pub(crate) fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// Starts ramping from the current gain to `target`, reaching it after `frames` frames.
+    pub(crate) fn set(&mut self, target: f32, frames: usize) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        if frames == 0 {
+            self.current = target;
+            self.left = 0;
+        } else {
+            self.step = (target - self.current) / frames as f32;
+            self.left = frames;
+        }
+    }
+
+    /// Returns the gain of the next frame.
+    pub(crate) fn next(&mut self) -> f32 {
+        let gain = self.current;
+        if self.left > 0 {
+            self.left -= 1;
+            self.current = match self.left {
+                0 => self.target,
+                _ => self.current + self.step,
+            };
+        }
+        gain
+    }
+
+    /// Moves the gain on by `frames` frames, returning the number of them it was ramping for.
+    pub(crate) fn skip(&mut self, frames: usize) -> usize {
+        let ramped = self.left.min(frames);
+        self.left -= ramped;
+        self.current = match self.left {
+            0 => self.target,
+            _ => self.current + self.step * ramped as f32,
+        };
+        ramped
+    }
+
+    /// Scales interleaved frames of `channels` channels by the gain.
+    pub(crate) fn apply(&mut self, buf: &mut [f32], channels: usize) {
+        let start = self.current;
+        let ramped = self.skip(buf.len() / channels);
+        if ramped > 0 {
+            simd::ramp_gain(&mut buf[..ramped * channels], channels, start, self.current);
+        }
+        if self.current != 1.0 {
+            let gain = self.current;
+            simd::ramp_gain(&mut buf[ramped * channels..], channels, gain, gain);
+        }
+    }
+
+    /// Scales stereo frames by the gain.
+    pub(crate) fn apply_stereo(&mut self, frames: &mut [(f32, f32)]) {
+        if self.left == 0 && self.current == 1.0 {
+            return;
+        }
+        for frame in frames {
+            let gain = self.next();
+            frame.0 *= gain;
+            frame.1 *= gain;
+        }
+    }
+}
+
+/// Returns the number of frames a ramp of the given length takes.
+pub(crate) fn frames(time: Duration) -> usize {
+    (time.as_secs_f64() * SAMPLE_RATE as f64).round() as usize
+}
@@ -0,0 +1,121 @@
+//! Noticing when the driver starts playing a device at a different sample rate, as PipeWire does
+//! when the rate of its graph changes.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use sdl2::audio::AudioSpec;
+
+/// How long the rate the device is asking for audio at is measured over. Long enough that a
+/// hitch or two barely changes the measurement.
+const WINDOW: Duration = Duration::from_secs(4);
+/// The sample rates a measurement is rounded to, which are the only rates drivers really run at.
+const RATES: [u32; 8] = [8000, 16000, 22050, 32000, 44100, 48000, 88200, 96000];
+/// How close a measurement must be to one of [`RATES`] to count.
+const TOLERANCE: f64 = 0.01;
+
+/// What an [`SdlSound`][crate::SdlSound] does when its device starts playing at a different
+/// sample rate than it was opened with, set with
+/// [`OpenOptions::rate_change`][crate::OpenOptions::rate_change].
+///
+/// Some drivers, like PipeWire, can change the rate of a device while it plays, without SDL
+/// noticing, so that everything plays at the wrong pitch. The rate is measured from how quickly
+/// the device asks for audio, during [`SdlSound::update`][crate::SdlSound::update], and once it
+/// has been seen at another common rate for two measurements in a row,
+/// [`AudioEvent::SampleRateChanged`][crate::AudioEvent::SampleRateChanged] is sent and the change
+/// is handled as this says.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RateChangePolicy {
+    /// Resample the output to the new rate, without interrupting playback.
+    #[default]
+    Resample,
+    /// Close and reopen the device, so that SDL converts to the new rate itself.
+    Reopen,
+}
+
+/// Measures the rate a device asks for audio at, from the number of times its callback runs.
+pub(crate) struct RateMonitor {
+    policy: RateChangePolicy,
+    /// The number of callbacks run, counted by the callback itself.
+    heartbeat: Arc<AtomicU64>,
+    /// The size of the device's buffer, in frames.
+    buffer_frames: u64,
+    /// When the current measurement started, and the heartbeat then.
+    start: Option<(Instant, u64)>,
+    /// The rate the device is believed to play at.
+    rate: u32,
+    /// A new rate seen in the last measurement, waiting to be seen again.
+    candidate: Option<u32>,
+}
+
+impl RateMonitor {
+    /// Creates a monitor for a device opened with the given spec.
+    pub(crate) fn new(
+        policy: RateChangePolicy,
+        heartbeat: Arc<AtomicU64>,
+        spec: &AudioSpec,
+    ) -> Self {
+        Self {
+            policy,
+            heartbeat,
+            buffer_frames: spec.samples.into(),
+            start: None,
+            rate: spec.freq as u32,
+            candidate: None,
+        }
+    }
+
+    pub(crate) fn policy(&self) -> RateChangePolicy {
+        self.policy
+    }
+
+    /// Starts measuring afresh, for when the device is paused or reopened. A reopened device
+    /// plays at the rate it was opened with, and may have a buffer of a different size, so is
+    /// given with its new spec.
+    pub(crate) fn restart(&mut self, reopened: Option<&AudioSpec>) {
+        self.start = None;
+        self.candidate = None;
+        if let Some(spec) = reopened {
+            self.buffer_frames = spec.samples.into();
+            self.rate = spec.freq as u32;
+        }
+    }
+
+    /// Measures the rate, if a measurement is due, and returns the new rate once it has changed.
+    pub(crate) fn check(&mut self, now: Instant) -> Option<u32> {
+        let count = self.heartbeat.load(Ordering::Relaxed);
+        let (start, start_count) = match self.start {
+            Some(start) => start,
+            None => {
+                self.start = Some((now, count));
+                return None;
+            }
+        };
+        let elapsed = now - start;
+        if elapsed < WINDOW {
+            return None;
+        }
+        self.start = Some((now, count));
+        let measured = (count - start_count) as f64 * self.buffer_frames as f64;
+        let measured = measured / elapsed.as_secs_f64();
+        let rate = RATES
+            .into_iter()
+            .find(|&rate| (measured / rate as f64 - 1.0).abs() < TOLERANCE);
+        match rate {
+            Some(rate) if rate != self.rate => {
+                if self.candidate.replace(rate) == Some(rate) {
+                    self.candidate = None;
+                    self.rate = rate;
+                    return Some(rate);
+                }
+            }
+            _ => self.candidate = None,
+        }
+        None
+    }
+}
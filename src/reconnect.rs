@@ -0,0 +1,63 @@
+//! Reopening lost playback devices.
+
+use std::time::Duration;
+
+/// How an [`SdlSound`][crate::SdlSound] tries to reopen its device after losing it, set with
+/// [`OpenOptions::reconnect`][crate::OpenOptions::reconnect].
+///
+/// Each attempt first tries the device that was originally opened, then the default device. The
+/// delay before each attempt starts at `initial_delay`, and doubles after every failed attempt up
+/// to `max_delay`. Progress is reported with [`AudioEvent`][crate::AudioEvent]s.
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use rg3d_sound_sdl::{DeviceSelector, OpenOptions, ReconnectPolicy};
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let mut sound = OpenOptions::new()
+///     .device(DeviceSelector::Name("USB Headset".into()))
+///     .reconnect(ReconnectPolicy {
+///         max_attempts: Some(10),
+///         ..Default::default()
+///     })
+///     .start_paused(false)
+///     .open(&audio)
+///     .unwrap();
+///
+/// // In the game loop:
+/// sound.update();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReconnectPolicy {
+    /// The delay before the first attempt.
+    pub initial_delay: Duration,
+    /// The longest delay between attempts.
+    pub max_delay: Duration,
+    /// The number of attempts to make before giving up, or `None` to keep trying forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay before the given attempt, counting from 1.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+impl Default for ReconnectPolicy {
+    /// Starts retrying after 250ms, backing off to every 10 seconds, forever.
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+            max_attempts: None,
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Recording a capture device, such as a microphone, straight to a WAV file.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use sdl2::{
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    AudioSubsystem,
+};
+
+use crate::simd;
+
+/// How often the writing thread saves what has been captured.
+const WRITE_INTERVAL: Duration = Duration::from_millis(50);
+/// How long the captured audio can wait to be written before the oldest is dropped.
+const MAX_BUFFERED: Duration = Duration::from_secs(5);
+
+/// The format of the WAV files written by a [`Recorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WavFormat {
+    /// The number of channels, 1 or 2.
+    pub channels: u8,
+    /// The sample rate, in Hz.
+    pub sample_rate: u32,
+    /// Whether samples are written as 32-bit floats, rather than 16-bit integers.
+    pub float: bool,
+}
+
+impl Default for WavFormat {
+    /// Mono 16-bit audio at 44.1kHz, which suits voice.
+    fn default() -> Self {
+        Self {
+            channels: 1,
+            sample_rate: 44100,
+            float: false,
+        }
+    }
+}
+
+impl WavFormat {
+    fn spec(self) -> WavSpec {
+        WavSpec {
+            channels: self.channels.into(),
+            sample_rate: self.sample_rate,
+            bits_per_sample: if self.float { 32 } else { 16 },
+            sample_format: if self.float {
+                SampleFormat::Float
+            } else {
+                SampleFormat::Int
+            },
+        }
+    }
+}
+
+/// Records a capture device, such as a microphone, to a WAV file, for example for voice memos or
+/// attaching a spoken description to a bug report.
+///
+/// SDL converts whatever the device captures to the channels and sample rate of the
+/// [`WavFormat`], and the samples are converted to the sample format of the file and written on a
+/// thread of their own, so the capture is never held up by the disk. The recording starts paused.
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::{Recorder, WavFormat};
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let recorder = Recorder::open(&audio, None, "memo.wav", WavFormat::default()).unwrap();
+/// recorder.start();
+///
+/// // Once the player lets go of the "record" key:
+/// let length = recorder.stop().unwrap();
+/// println!("Recorded {:.1}s", length.as_secs_f32());
+/// ```
+pub struct Recorder {
+    device: Option<AudioDevice<RecorderCallback>>,
+    path: PathBuf,
+    format: WavFormat,
+    shared: Arc<Shared>,
+    writer: Option<JoinHandle<Result<u64, String>>>,
+}
+
+struct Shared {
+    /// The captured samples waiting to be written.
+    ring: Mutex<VecDeque<f32>>,
+    capacity: usize,
+    /// Whether the recording has been stopped, so the writing thread should finish the file.
+    stopped: AtomicBool,
+}
+
+impl Recorder {
+    /// Opens a capture device, by name, or the default capture device with `None`, and creates a
+    /// WAV file at `path` to record it to, replacing any file already there. On error, returns the
+    /// SDL error, in which case no file is created, or why the file couldn't be created.
+    /// # Panics
+    /// This function will panic if the format doesn't have 1 or 2 channels.
+    pub fn open(
+        subsystem: &AudioSubsystem,
+        device: Option<&str>,
+        path: impl AsRef<Path>,
+        format: WavFormat,
+    ) -> Result<Self, String> {
+        assert!(
+            format.channels == 1 || format.channels == 2,
+            "Recordings must be mono or stereo"
+        );
+        let shared = Arc::new(Shared {
+            ring: Mutex::new(VecDeque::new()),
+            capacity: (MAX_BUFFERED.as_secs_f64() * f64::from(format.sample_rate)) as usize
+                * usize::from(format.channels),
+            stopped: AtomicBool::new(false),
+        });
+        let desired = AudioSpecDesired {
+            freq: Some(format.sample_rate as i32),
+            channels: Some(format.channels),
+            samples: None,
+        };
+        let device = subsystem.open_capture(device, &desired, |_| RecorderCallback {
+            shared: Arc::clone(&shared),
+        })?;
+        // Created once the device is open, so that failing to open it leaves no empty file.
+        let path = path.as_ref().to_path_buf();
+        let file = WavWriter::create(&path, format.spec())
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let writer = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("rg3d-sound-sdl recorder".into())
+                .spawn(move || write(&shared, file, format))
+                .expect("Failed to spawn recorder thread")
+        };
+        Ok(Self {
+            device: Some(device),
+            path,
+            format,
+            shared,
+            writer: Some(writer),
+        })
+    }
+
+    /// Returns the path of the file being recorded to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the format of the file being recorded to.
+    pub fn format(&self) -> WavFormat {
+        self.format
+    }
+
+    /// Starts or continues recording.
+    pub fn start(&self) {
+        if let Some(device) = &self.device {
+            device.resume();
+        }
+    }
+
+    /// Pauses recording, leaving nothing in the file for the time it's paused.
+    pub fn pause(&self) {
+        if let Some(device) = &self.device {
+            device.pause();
+        }
+    }
+
+    /// Returns whether the recorder is recording, rather than paused.
+    pub fn is_recording(&self) -> bool {
+        self.device
+            .as_ref()
+            .is_some_and(|d| d.status() == sdl2::audio::AudioStatus::Playing)
+    }
+
+    /// Stops recording, closing the capture device and finishing the file. Returns the length of
+    /// the recording, or on error, why the file couldn't be written.
+    pub fn stop(mut self) -> Result<Duration, String> {
+        let frames = self.finish()?;
+        Ok(Duration::from_secs_f64(
+            frames as f64 / f64::from(self.format.sample_rate),
+        ))
+    }
+
+    fn finish(&mut self) -> Result<u64, String> {
+        // Closing the device first means nothing more is captured once the rest is written.
+        self.device = None;
+        self.shared.stopped.store(true, Ordering::Release);
+        match self.writer.take() {
+            Some(writer) => writer
+                .join()
+                .unwrap_or_else(|_| Err("Recorder thread panicked".into())),
+            None => Ok(0),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Writes captured samples to the file until the recording is stopped, then finishes the file.
+/// Returns the number of frames written.
+fn write(
+    shared: &Shared,
+    mut file: WavWriter<BufWriter<File>>,
+    format: WavFormat,
+) -> Result<u64, String> {
+    let mut samples = Vec::new();
+    let mut converted = Vec::new();
+    let mut written = 0;
+    loop {
+        // Checked before draining, so that everything captured before the stop is written.
+        let stopped = shared.stopped.load(Ordering::Acquire);
+        samples.extend(shared.ring.lock().unwrap().drain(..));
+        let result = if format.float {
+            samples
+                .iter()
+                .try_for_each(|&sample| file.write_sample(sample))
+        } else {
+            converted.resize(samples.len(), 0);
+            simd::f32_to_i16(&samples, &mut converted);
+            converted
+                .iter()
+                .try_for_each(|&sample| file.write_sample(sample))
+        };
+        result.map_err(|e| format!("Failed to write recording: {}", e))?;
+        written += samples.len() as u64;
+        samples.clear();
+        if stopped {
+            break;
+        }
+        thread::sleep(WRITE_INTERVAL);
+    }
+    file.finalize()
+        .map_err(|e| format!("Failed to write recording: {}", e))?;
+    Ok(written / u64::from(format.channels))
+}
+
+/// The [`AudioCallback`] of a [`Recorder`], which buffers the captured samples for writing.
+struct RecorderCallback {
+    shared: Arc<Shared>,
+}
+
+impl AudioCallback for RecorderCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, buf: &mut [f32]) {
+        let mut ring = self.shared.ring.lock().unwrap();
+        ring.extend(buf.iter());
+        let excess = ring.len().saturating_sub(self.shared.capacity);
+        ring.drain(..excess);
+    }
+}
@@ -0,0 +1,42 @@
+//! Sources of rendered audio for a [`Callback`][crate::Callback].
+
+use std::sync::PoisonError;
+
+use crate::EngineHandle;
+
+/// Something which renders blocks of stereo audio for a [`Callback`][crate::Callback] to play.
+///
+/// This is implemented for [`EngineHandle`], which is what a callback renders by default, but
+/// custom implementations can be used to test code built on the callback without a
+/// [`SoundEngine`][rg3d_sound::engine::SoundEngine], or to interpose adapters in front of one.
+/// # Example
+/// ```
+/// use rg3d_sound_sdl::{BlockRenderer, Callback};
+///
+/// /// Renders a square wave at the Nyquist frequency.
+/// struct Square(f32);
+///
+/// impl BlockRenderer for Square {
+///     fn render(&mut self, buf: &mut [(f32, f32)]) {
+///         for frame in buf {
+///             self.0 = -self.0;
+///             *frame = (self.0, self.0);
+///         }
+///     }
+/// }
+///
+/// let callback = Callback::new(Square(0.5));
+/// ```
+pub trait BlockRenderer: Send {
+    /// Renders the next `buf.len()` frames of audio into `buf`, overwriting its contents.
+    fn render(&mut self, buf: &mut [(f32, f32)]);
+}
+
+impl BlockRenderer for EngineHandle {
+    fn render(&mut self, buf: &mut [(f32, f32)]) {
+        // Keep playing if a panic elsewhere poisoned the engine.
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .render(buf);
+    }
+}
@@ -0,0 +1,146 @@
+//! Routing contexts to different playback devices.
+
+use rg3d_sound::context::SoundContext;
+
+use crate::SdlSound;
+
+/// Identifies an output added to a [`ContextRouter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OutputId(usize);
+
+/// Plays different contexts on different devices, for example so that a streamer can send music
+/// to a virtual device which is left out of the stream, while voice and sound effects go to the
+/// device that is streamed.
+///
+/// Each output is an [`SdlSound`] with an engine of its own, and each context is attached to the
+/// engine of exactly one output, so each device renders only the contexts routed to it. Routing a
+/// context to another output moves it between engines, cutting straight over to the new device,
+/// and every source in it carries on from where it was.
+/// # Example
+/// ```no_run
+/// use rg3d_sound::context::SoundContext;
+/// use rg3d_sound_sdl::{ContextRouter, DeviceSelector, OpenOptions};
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let mut router = ContextRouter::new();
+/// let game = router.add_output(OpenOptions::new().start_paused(false).open(&audio).unwrap());
+/// let music = router.add_output(
+///     OpenOptions::new()
+///         .device(DeviceSelector::Name("Music Sink".into()))
+///         .start_paused(false)
+///         .open(&audio)
+///         .unwrap(),
+/// );
+///
+/// let sfx_ctx = SoundContext::new();
+/// let music_ctx = SoundContext::new();
+/// router.route(&sfx_ctx, game);
+/// router.route(&music_ctx, music);
+///
+/// // In the game loop:
+/// router.update();
+/// ```
+#[derive(Default)]
+pub struct ContextRouter {
+    outputs: Vec<Option<SdlSound>>,
+}
+
+impl ContextRouter {
+    /// Creates a router without any outputs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an output, which keeps any contexts already attached to its engine.
+    pub fn add_output(&mut self, sound: SdlSound) -> OutputId {
+        self.outputs.push(Some(sound));
+        OutputId(self.outputs.len() - 1)
+    }
+
+    /// Removes an output, returning it with the contexts routed to it still attached, or `None`
+    /// if it was already removed.
+    pub fn remove_output(&mut self, id: OutputId) -> Option<SdlSound> {
+        self.outputs.get_mut(id.0)?.take()
+    }
+
+    /// Returns an output, or `None` if it has been removed.
+    pub fn output(&self, id: OutputId) -> Option<&SdlSound> {
+        self.outputs.get(id.0)?.as_ref()
+    }
+
+    /// Returns an output mutably, or `None` if it has been removed.
+    pub fn output_mut(&mut self, id: OutputId) -> Option<&mut SdlSound> {
+        self.outputs.get_mut(id.0)?.as_mut()
+    }
+
+    /// Returns the IDs of the outputs, in the order they were added.
+    pub fn output_ids(&self) -> impl Iterator<Item = OutputId> + '_ {
+        self.outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.is_some())
+            .map(|(i, _)| OutputId(i))
+    }
+
+    /// Plays a context on the given output, detaching it from whichever output it was routed to
+    /// before.
+    /// # Panics
+    /// This function will panic if the output has been removed.
+    pub fn route(&mut self, context: &SoundContext, to: OutputId) {
+        assert!(self.output(to).is_some(), "No such output");
+        if let Some(from) = self.output_of(context) {
+            if from == to {
+                return;
+            }
+            let sound = self.output(from).unwrap();
+            sound
+                .engine()
+                .lock()
+                .unwrap()
+                .remove_context(context.clone());
+        }
+        let sound = self.output(to).unwrap();
+        sound.engine().lock().unwrap().add_context(context.clone());
+    }
+
+    /// Stops playing a context on any output. Returns whether it was routed anywhere.
+    pub fn unroute(&mut self, context: &SoundContext) -> bool {
+        match self.output_of(context) {
+            Some(from) => {
+                let sound = self.output(from).unwrap();
+                sound
+                    .engine()
+                    .lock()
+                    .unwrap()
+                    .remove_context(context.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the output a context is routed to, or `None` if it isn't routed anywhere.
+    pub fn output_of(&self, context: &SoundContext) -> Option<OutputId> {
+        self.output_ids().find(|&id| {
+            let sound = self.output(id).unwrap();
+            let has_context = sound.engine().lock().unwrap().has_context(context);
+            has_context
+        })
+    }
+
+    /// Calls [`SdlSound::update`] on every output.
+    pub fn update(&mut self) {
+        self.outputs.iter_mut().flatten().for_each(SdlSound::update);
+    }
+
+    /// Resumes every output.
+    pub fn resume(&self) {
+        self.outputs.iter().flatten().for_each(SdlSound::resume);
+    }
+
+    /// Pauses every output.
+    pub fn pause(&self) {
+        self.outputs.iter().flatten().for_each(SdlSound::pause);
+    }
+}
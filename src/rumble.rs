@@ -0,0 +1,145 @@
+//! Driving controller rumble from the low frequencies of the output of a
+//! [`Callback`][crate::Callback].
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use sdl2::controller::GameController;
+
+use crate::dsp::Biquad;
+
+/// How much of the previous level is kept after each block, so that rumble dies away smoothly
+/// rather than stopping dead between blasts.
+const RELEASE: f32 = 0.6;
+
+/// A tap measuring the low-frequency energy of the final mixed output of a
+/// [`Callback`][crate::Callback], so that explosions and bass can shake a controller without a
+/// separate event system.
+///
+/// The level is measured once per block, after low-passing the output, and scaled so that a full
+/// scale tone below the cutoff gives a level of 1. The handle can be cloned and sent to any
+/// thread, and all clones refer to the same tap. Add it to a callback with
+/// [`Callback::add_rumble`][crate::Callback::add_rumble].
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::Rumble;
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let controllers = sdl.game_controller().unwrap();
+/// let mut controller = controllers.open(0).unwrap();
+/// let (engine, mut device) = rg3d_sound_sdl::open(&audio, None).unwrap();
+///
+/// let rumble = Rumble::new(120.0);
+/// rumble.set_sensitivity(2.0);
+/// device.lock().add_rumble(rumble.clone());
+/// device.resume();
+///
+/// // Once per frame:
+/// rumble.drive(&mut controller, 100).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Rumble {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    cutoff: f32,
+    /// The level and sensitivity as the bits of `f32`s.
+    level: AtomicU32,
+    sensitivity: AtomicU32,
+}
+
+impl Rumble {
+    /// Creates a tap measuring frequencies below `cutoff`, in Hz. Around 100-150Hz suits most
+    /// controllers.
+    pub fn new(cutoff: f32) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                cutoff,
+                level: AtomicU32::new(0.0f32.to_bits()),
+                sensitivity: AtomicU32::new(1.0f32.to_bits()),
+            }),
+        }
+    }
+
+    /// Returns the cutoff frequency, in Hz.
+    pub fn cutoff(&self) -> f32 {
+        self.shared.cutoff
+    }
+
+    /// Sets how strongly the measured energy is scaled before being clamped to 1. Defaults to 1.
+    pub fn set_sensitivity(&self, sensitivity: f32) {
+        self.shared
+            .sensitivity
+            .store(sensitivity.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns how strongly the measured energy is scaled.
+    pub fn sensitivity(&self) -> f32 {
+        f32::from_bits(self.shared.sensitivity.load(Ordering::Relaxed))
+    }
+
+    /// Returns the current rumble level, between 0 and 1.
+    pub fn level(&self) -> f32 {
+        f32::from_bits(self.shared.level.load(Ordering::Relaxed))
+    }
+
+    /// Sets the low-frequency motor of `controller` to the current level for `duration_ms`
+    /// milliseconds. Call this regularly with a duration a little longer than the time between
+    /// calls, so that the rumble follows the audio without gaps. On error, returns the SDL error.
+    pub fn drive(&self, controller: &mut GameController, duration_ms: u32) -> Result<(), String> {
+        let strength = (self.level() * u16::MAX as f32) as u16;
+        controller
+            .set_rumble(strength, 0, duration_ms)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Returns whether two handles refer to the same tap.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+}
+
+/// A [`Rumble`] tap along with the state of its filter, owned by the callback.
+pub(crate) struct RumbleTap {
+    rumble: Rumble,
+    filter: Biquad,
+}
+
+impl RumbleTap {
+    pub(crate) fn new(rumble: Rumble) -> Self {
+        Self {
+            filter: Biquad::low_pass(rumble.cutoff()),
+            rumble,
+        }
+    }
+
+    pub(crate) fn rumble(&self) -> &Rumble {
+        &self.rumble
+    }
+
+    /// Measures a block of interleaved frames, updating the level of the tap.
+    pub(crate) fn measure(&mut self, frames: &[f32], channels: usize) {
+        let mut sum = 0.0;
+        let mut len = 0;
+        for frame in frames.chunks_exact(channels) {
+            let mono = frame.iter().sum::<f32>() / channels as f32;
+            let low = self.filter.process(mono);
+            sum += low * low;
+            len += 1;
+        }
+        if len == 0 {
+            return;
+        }
+        let rms = (sum / len as f32).sqrt() * std::f32::consts::SQRT_2;
+        let measured = (rms * self.rumble.sensitivity()).min(1.0);
+        let level = measured.max(self.rumble.level() * RELEASE);
+        self.rumble
+            .shared
+            .level
+            .store(level.to_bits(), Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,227 @@
+//! Loading sounds from SDL-managed storage through [`RWops`].
+
+use std::{
+    fmt,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+    vec,
+};
+
+use hound::{SampleFormat, WavReader};
+use lewton::{inside_ogg::OggStreamReader, samples::InterleavedSamples};
+use rg3d_sound::{
+    buffer::{DataSource, RawStreamingDataSource},
+    error::SoundError,
+};
+use sdl2::rwops::RWops;
+
+/// Wraps an SDL [`RWops`] as a [`Read`] + [`Seek`] stream which can be sent between threads, so
+/// that sounds can be loaded from anything SDL can open, including packed asset formats and
+/// Android APK assets.
+///
+/// `rg3d_sound`'s [`DataSource`] can't read from arbitrary streams, so sounds are decoded by this
+/// crate instead (WAV and Ogg Vorbis are supported, like `rg3d_sound` itself). Use
+/// [`RwopsReader::into_streaming_source`] to decode on the fly without reading the whole asset
+/// into memory, or [`RwopsReader::into_raw_source`] to decode it up front for a generic buffer.
+/// # Example
+/// ```no_run
+/// use rg3d_sound::buffer::SoundBufferResource;
+/// use rg3d_sound_sdl::RwopsReader;
+///
+/// let music = RwopsReader::from_file("music.ogg").unwrap();
+/// let buffer = SoundBufferResource::new_streaming(music.into_streaming_source().unwrap());
+///
+/// let ding = RwopsReader::from_file("ding.wav").unwrap();
+/// let buffer = SoundBufferResource::new_generic(ding.into_raw_source().unwrap());
+/// ```
+pub struct RwopsReader {
+    rwops: RWops<'static>,
+}
+
+// SAFETY: SDL_RWops have no affinity to the thread that created them, and an `RwopsReader` only
+// ever accesses its `RWops` through `&mut self`, so it can't be used from two threads at once.
+unsafe impl Send for RwopsReader {}
+unsafe impl Sync for RwopsReader {}
+
+impl RwopsReader {
+    /// Wraps an existing [`RWops`].
+    pub fn new(rwops: RWops<'static>) -> Self {
+        Self { rwops }
+    }
+
+    /// Opens a file for reading with [`RWops::from_file`]. On Android, this also finds files in
+    /// the APK's assets. On error, returns the SDL error.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        RWops::from_file(path, "rb").map(Self::new)
+    }
+
+    /// Returns the wrapped [`RWops`].
+    pub fn into_inner(self) -> RWops<'static> {
+        self.rwops
+    }
+
+    /// Creates a [`DataSource`] for use with a streaming buffer, which decodes the sound as it is
+    /// played. On error, returns a description of why the sound couldn't be decoded.
+    pub fn into_streaming_source(self) -> Result<DataSource, String> {
+        Decoder::new(self).map(|decoder| DataSource::RawStreaming(Box::new(decoder)))
+    }
+
+    /// Decodes the whole sound into a [`DataSource`] for use with a generic buffer. On error,
+    /// returns a description of why the sound couldn't be decoded.
+    pub fn into_raw_source(self) -> Result<DataSource, String> {
+        let decoder = Decoder::new(self)?;
+        let sample_rate = decoder.sample_rate();
+        let channel_count = decoder.channel_count();
+        Ok(DataSource::Raw {
+            sample_rate,
+            channel_count,
+            samples: decoder.collect(),
+        })
+    }
+}
+
+impl Read for RwopsReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.rwops.read(buf)
+    }
+}
+
+impl Seek for RwopsReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.rwops.seek(pos)
+    }
+}
+
+/// Decodes samples from an [`RwopsReader`].
+enum Decoder {
+    Wav(WavReader<RwopsReader>),
+    Vorbis {
+        reader: Box<OggStreamReader<RwopsReader>>,
+        samples: vec::IntoIter<f32>,
+    },
+}
+
+impl Decoder {
+    fn new(mut reader: RwopsReader) -> Result<Self, String> {
+        let start = reader.stream_position().map_err(|e| e.to_string())?;
+        if WavReader::new(&mut reader).is_ok() {
+            reader
+                .seek(SeekFrom::Start(start))
+                .map_err(|e| e.to_string())?;
+            return WavReader::new(reader)
+                .map(Self::Wav)
+                .map_err(|e| e.to_string());
+        }
+
+        reader
+            .seek(SeekFrom::Start(start))
+            .map_err(|e| e.to_string())?;
+        match OggStreamReader::new(reader) {
+            Ok(reader) => Ok(Self::Vorbis {
+                reader: Box::new(reader),
+                samples: Vec::new().into_iter(),
+            }),
+            Err(_) => Err("Unsupported sound format, expected WAV or Ogg Vorbis".into()),
+        }
+    }
+}
+
+impl fmt::Debug for Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wav(_) => write!(f, "RwopsWavDecoder"),
+            Self::Vorbis { .. } => write!(f, "RwopsVorbisDecoder"),
+        }
+    }
+}
+
+impl Iterator for Decoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        match self {
+            Self::Wav(reader) => {
+                let spec = reader.spec();
+                match (spec.sample_format, spec.bits_per_sample) {
+                    (SampleFormat::Float, _) => reader.samples::<f32>().next()?.ok(),
+                    (SampleFormat::Int, 8) => reader
+                        .samples::<i8>()
+                        .next()?
+                        .ok()
+                        .map(|s| s as f32 / i8::MAX as f32),
+                    (SampleFormat::Int, 16) => reader
+                        .samples::<i16>()
+                        .next()?
+                        .ok()
+                        .map(|s| s as f32 / i16::MAX as f32),
+                    (SampleFormat::Int, bits) => {
+                        let max = ((1i64 << (bits - 1)) - 1) as f32;
+                        reader.samples::<i32>().next()?.ok().map(|s| s as f32 / max)
+                    }
+                }
+            }
+            Self::Vorbis { reader, samples } => loop {
+                if let Some(sample) = samples.next() {
+                    return Some(sample);
+                }
+                let packet = reader
+                    .read_dec_packet_generic::<InterleavedSamples<f32>>()
+                    .ok()??;
+                *samples = packet.samples.into_iter();
+            },
+        }
+    }
+}
+
+impl RawStreamingDataSource for Decoder {
+    fn sample_rate(&self) -> usize {
+        match self {
+            Self::Wav(reader) => reader.spec().sample_rate as usize,
+            Self::Vorbis { reader, .. } => reader.ident_hdr.audio_sample_rate as usize,
+        }
+    }
+
+    fn channel_count(&self) -> usize {
+        match self {
+            Self::Wav(reader) => reader.spec().channels as usize,
+            Self::Vorbis { reader, .. } => reader.ident_hdr.audio_channels as usize,
+        }
+    }
+
+    fn rewind(&mut self) -> Result<(), SoundError> {
+        match self {
+            Self::Wav(reader) => reader.seek(0)?,
+            Self::Vorbis { reader, samples } => {
+                reader
+                    .seek_absgp_pg(0)
+                    .map_err(|_| SoundError::UnsupportedFormat)?;
+                *samples = Vec::new().into_iter();
+            }
+        }
+        Ok(())
+    }
+
+    fn time_seek(&mut self, location: Duration) {
+        let frame = location.as_secs_f64() * self.sample_rate() as f64;
+        match self {
+            Self::Wav(reader) => {
+                let _ = reader.seek(frame as u32);
+            }
+            Self::Vorbis { reader, samples } => {
+                if reader.seek_absgp_pg(frame as u64).is_ok() {
+                    *samples = Vec::new().into_iter();
+                }
+            }
+        }
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        match self {
+            Self::Wav(reader) => Some(Duration::from_secs_f64(
+                reader.duration() as f64 / reader.spec().sample_rate as f64,
+            )),
+            Self::Vorbis { .. } => None,
+        }
+    }
+}
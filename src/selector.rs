@@ -0,0 +1,154 @@
+//! Selection of SDL playback devices.
+
+use std::{ffi::CStr, fmt, os::raw::c_int, path::PathBuf, str::FromStr};
+
+use sdl2::sys;
+
+use crate::driver;
+
+/// Selects which SDL playback device to open.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeviceSelector {
+    /// The system's default playback device, as chosen by SDL.
+    #[default]
+    Default,
+    /// The playback device at the given index, as enumerated by
+    /// [`AudioSubsystem::audio_playback_device_name`][sdl2::AudioSubsystem::audio_playback_device_name].
+    Index(u32),
+    /// The playback device with the given name.
+    Name(String),
+    /// The default device of SDL's dummy driver, which discards all audio at the correct rate.
+    /// This is useful as a last resort, so that a game can still run without working audio.
+    ///
+    /// Opening this selector switches SDL to the dummy driver. Switching drivers closes every open
+    /// audio device, so opening fails while any are open, except that
+    /// [`SdlSound::switch_device`][crate::SdlSound::switch_device] and
+    /// [`SdlSound::reopen`][crate::SdlSound::reopen] close their own device first. Opening any
+    /// other selector afterwards switches back to the driver in use before.
+    Dummy,
+    /// The device of SDL's disk driver, which writes the raw samples it plays to the given file,
+    /// in the format of the obtained spec, in real time. Set the `SDL_DISKAUDIODELAY`
+    /// environment variable to `0` before opening to write as fast as blocks can be rendered.
+    ///
+    /// Opening this selector switches SDL to the disk driver, with the same restrictions as
+    /// [`Dummy`][DeviceSelector::Dummy].
+    Disk(PathBuf),
+}
+
+impl DeviceSelector {
+    /// Parses a selector from a string, for use in command line flags and config files. The
+    /// grammar is `default`, `dummy`, `index:<index>`, `name:<device name>` or `disk:<path>`, and
+    /// [`DeviceSelector`]'s [`Display`][fmt::Display] implementation writes the same grammar. On
+    /// error, returns a description of why the string isn't a valid selector.
+    /// # Example
+    /// ```
+    /// use rg3d_sound_sdl::DeviceSelector;
+    ///
+    /// assert_eq!(DeviceSelector::parse("default"), Ok(DeviceSelector::Default));
+    /// assert_eq!(DeviceSelector::parse("index:2"), Ok(DeviceSelector::Index(2)));
+    /// assert_eq!(
+    ///     DeviceSelector::parse("name:HDA Intel PCH"),
+    ///     Ok(DeviceSelector::Name("HDA Intel PCH".into()))
+    /// );
+    /// assert_eq!(
+    ///     DeviceSelector::parse("disk:out.raw"),
+    ///     Ok(DeviceSelector::Disk("out.raw".into()))
+    /// );
+    /// assert!(DeviceSelector::parse("index:first").is_err());
+    /// assert!(DeviceSelector::parse("name:").is_err());
+    /// assert_eq!(DeviceSelector::Index(2).to_string(), "index:2");
+    /// ```
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.split_once(':') {
+            _ if s == "default" => Ok(Self::Default),
+            _ if s == "dummy" => Ok(Self::Dummy),
+            Some(("index", index)) => index
+                .parse()
+                .map(Self::Index)
+                .map_err(|_| format!("Invalid device index: {}", index)),
+            Some(("name", "")) => Err("Empty device name".into()),
+            Some(("name", name)) => Ok(Self::Name(name.into())),
+            Some(("disk", path)) => Ok(Self::Disk(path.into())),
+            _ => Err(format!(
+                "Invalid device selector {:?}, expected default, dummy, index:<index>, name:<name> or disk:<path>",
+                s
+            )),
+        }
+    }
+
+    /// Returns the driver SDL must be switched to before opening the device this selects with the
+    /// given driver, or `None` if the current driver will do. The dummy and disk selectors need
+    /// their own drivers, and other selectors switch back from them to the driver that was in use
+    /// before, unless another driver was asked for.
+    pub(crate) fn required_driver(&self, driver: Option<&str>) -> Option<String> {
+        let current = driver::current();
+        let required = match self {
+            Self::Dummy => Some("dummy".into()),
+            Self::Disk(_) => Some("disk".into()),
+            _ => driver.map(str::to_owned).or_else(|| {
+                driver::real_driver().filter(|_| current.is_none_or(driver::is_virtual))
+            }),
+        };
+        required.filter(|driver| Some(driver.as_str()) != current)
+    }
+
+    /// Resolves this selector to the device name that should be passed to SDL, where `None`
+    /// means the default device, first switching drivers as with
+    /// [`DeviceSelector::required_driver`], which fails while any audio device is open. The audio
+    /// subsystem must be initialized, but this can be called from any thread. On error, returns
+    /// the SDL error, or why the driver couldn't be switched.
+    pub(crate) fn resolve(&self, driver: Option<&str>) -> Result<Option<String>, String> {
+        if let Some(driver) = self.required_driver(driver) {
+            driver::init_driver(&driver, 0)?;
+        }
+        match self {
+            Self::Default | Self::Dummy => Ok(None),
+            Self::Index(index) => playback_device_name(*index).map(Some),
+            Self::Name(name) => Ok(Some(name.clone())),
+            Self::Disk(path) => path_name(path).map(Some),
+        }
+    }
+}
+
+impl FromStr for DeviceSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => write!(f, "default"),
+            Self::Index(index) => write!(f, "index:{}", index),
+            Self::Name(name) => write!(f, "name:{}", name),
+            Self::Dummy => write!(f, "dummy"),
+            Self::Disk(path) => write!(f, "disk:{}", path.display()),
+        }
+    }
+}
+
+/// Returns the name of the playback device at `index`, as
+/// [`AudioSubsystem::audio_playback_device_name`][sdl2::AudioSubsystem::audio_playback_device_name]
+/// does, without needing the subsystem's handle.
+fn playback_device_name(index: u32) -> Result<String, String> {
+    // SAFETY: SDL returns either null or a name it owns until devices are enumerated again, which
+    // is copied straight away.
+    unsafe {
+        let name = sys::SDL_GetAudioDeviceName(index as c_int, 0);
+        if name.is_null() {
+            return Err(sdl2::get_error());
+        }
+        Ok(CStr::from_ptr(name).to_string_lossy().into_owned())
+    }
+}
+
+/// Returns the name the disk driver opens a file by, as it takes device names as paths.
+fn path_name(path: &std::path::Path) -> Result<String, String> {
+    path.to_str()
+        .map(str::to_owned)
+        .ok_or_else(|| format!("Path is not valid UTF-8: {}", path.display()))
+}
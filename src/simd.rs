@@ -0,0 +1,320 @@
+//! SIMD implementations of the per-sample loops of the callback, with scalar fallbacks.
+//!
+//! SSE2 is used on x86_64 and NEON on aarch64, both of which every CPU of those architectures
+//! has. On other targets, and for channel counts the SIMD paths don't handle, the scalar code in
+//! [`scalar`] is used. This module is only public so that the benchmarks can compare the two.
+
+/// Converts samples to 16-bit, clamping them to [-1, 1] and rounding towards zero. NaN becomes
+/// zero.
+/// # Panics
+/// This function will panic if `dst` is shorter than `src`.
+pub fn f32_to_i16(src: &[f32], dst: &mut [i16]) {
+    assert!(dst.len() >= src.len(), "Destination too short");
+    let done = arch::f32_to_i16(src, dst);
+    scalar::f32_to_i16(&src[done..], &mut dst[done..]);
+}
+
+/// Scales interleaved frames of `channels` channels by a gain ramping linearly from `start` on
+/// the first frame towards `end`, as if it reached `end` on the frame after the last.
+pub fn ramp_gain(buf: &mut [f32], channels: usize, start: f32, end: f32) {
+    let frames = buf.len() / channels;
+    if frames == 0 {
+        return;
+    }
+    let step = (end - start) / frames as f32;
+    let done = arch::ramp_gain(buf, channels, start, step);
+    scalar::ramp_gain_from(buf, channels, start, step, done);
+}
+
+/// Folds interleaved frames with one channel for each of `gains` down to stereo, overwriting
+/// `to`. Each channel is mixed into the left and right channels at its pair of gains.
+pub fn fold_to_stereo(from: &[f32], gains: &[[f32; 2]], to: &mut [(f32, f32)]) {
+    let done = arch::fold_to_stereo(from, gains, to);
+    scalar::fold_to_stereo(&from[done * gains.len()..], gains, &mut to[done..]);
+}
+
+/// The scalar implementations, used where there's no SIMD implementation.
+pub mod scalar {
+    /// Converts samples to 16-bit, as [`f32_to_i16`][super::f32_to_i16] does.
+    pub fn f32_to_i16(src: &[f32], dst: &mut [i16]) {
+        for (out, &sample) in dst.iter_mut().zip(src) {
+            *out = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        }
+    }
+
+    /// Ramps the gain of frames, as [`ramp_gain`][super::ramp_gain] does.
+    pub fn ramp_gain(buf: &mut [f32], channels: usize, start: f32, end: f32) {
+        let frames = buf.len() / channels;
+        if frames > 0 {
+            ramp_gain_from(buf, channels, start, (end - start) / frames as f32, 0);
+        }
+    }
+
+    /// Ramps the gain of the frames from sample `from` on, which must start a frame.
+    pub(super) fn ramp_gain_from(
+        buf: &mut [f32],
+        channels: usize,
+        start: f32,
+        step: f32,
+        from: usize,
+    ) {
+        let first = from / channels;
+        for (i, frame) in buf[from..].chunks_exact_mut(channels).enumerate() {
+            let gain = start + step * (first + i) as f32;
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+    }
+
+    /// Folds frames down to stereo, as [`fold_to_stereo`][super::fold_to_stereo] does.
+    pub fn fold_to_stereo(from: &[f32], gains: &[[f32; 2]], to: &mut [(f32, f32)]) {
+        for (frame, out) in from.chunks_exact(gains.len()).zip(to) {
+            *out = (0.0, 0.0);
+            for (&sample, [left, right]) in frame.iter().zip(gains) {
+                out.0 += sample * left;
+                out.1 += sample * right;
+            }
+        }
+    }
+}
+
+/// The gain of each lane of a vector of four samples, in frames after the frame the vector
+/// starts on, for channel counts which divide four.
+fn lane_frames(channels: usize) -> Option<[f32; 4]> {
+    match channels {
+        1 => Some([0.0, 1.0, 2.0, 3.0]),
+        2 => Some([0.0, 0.0, 1.0, 1.0]),
+        4 => Some([0.0; 4]),
+        _ => None,
+    }
+}
+
+/// Pads the gains of up to eight channels to eight, split into those for the left and right.
+fn padded_gains(gains: &[[f32; 2]]) -> ([f32; 8], [f32; 8]) {
+    let (mut left, mut right) = ([0.0; 8], [0.0; 8]);
+    for (i, [l, r]) in gains.iter().enumerate() {
+        left[i] = *l;
+        right[i] = *r;
+    }
+    (left, right)
+}
+
+/// Each function processes as much as it can, and returns how much it processed: the number of
+/// samples for conversion and gain, and the number of frames for folding.
+#[cfg(target_arch = "x86_64")]
+mod arch {
+    use std::arch::x86_64::*;
+
+    use super::{lane_frames, padded_gains};
+
+    pub(super) fn f32_to_i16(src: &[f32], dst: &mut [i16]) -> usize {
+        let len = src.len() & !7;
+        // SAFETY: SSE2 is part of x86_64, and every load and store is within the first `len`
+        // elements of both slices.
+        unsafe {
+            let (min, max) = (_mm_set1_ps(-1.0), _mm_set1_ps(1.0));
+            let scale = _mm_set1_ps(i16::MAX as f32);
+            let convert = |ptr: *const f32| {
+                let x = _mm_loadu_ps(ptr);
+                let x = _mm_and_ps(x, _mm_cmpord_ps(x, x));
+                _mm_cvttps_epi32(_mm_mul_ps(_mm_max_ps(_mm_min_ps(x, max), min), scale))
+            };
+            for i in (0..len).step_by(8) {
+                let lo = convert(src.as_ptr().add(i));
+                let hi = convert(src.as_ptr().add(i + 4));
+                _mm_storeu_si128(dst.as_mut_ptr().add(i).cast(), _mm_packs_epi32(lo, hi));
+            }
+        }
+        len
+    }
+
+    // `is_multiple_of` would raise the MSRV to 1.87.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub(super) fn ramp_gain(buf: &mut [f32], channels: usize, start: f32, step: f32) -> usize {
+        // SAFETY: SSE2 is part of x86_64, and every load and store is within `buf`.
+        unsafe {
+            let (vstart, vstep) = (_mm_set1_ps(start), _mm_set1_ps(step));
+            if let Some(lanes) = lane_frames(channels) {
+                let lanes = _mm_loadu_ps(lanes.as_ptr());
+                let len = buf.len() & !3;
+                for i in (0..len).step_by(4) {
+                    let frame = _mm_add_ps(_mm_set1_ps((i / channels) as f32), lanes);
+                    let gain = _mm_add_ps(vstart, _mm_mul_ps(vstep, frame));
+                    let ptr = buf.as_mut_ptr().add(i);
+                    _mm_storeu_ps(ptr, _mm_mul_ps(_mm_loadu_ps(ptr), gain));
+                }
+                len
+            } else if channels % 2 == 0 {
+                for (i, frame) in buf.chunks_exact_mut(channels).enumerate() {
+                    let gain = _mm_set1_ps(start + step * i as f32);
+                    for j in (0..channels & !3).step_by(4) {
+                        let ptr = frame.as_mut_ptr().add(j);
+                        _mm_storeu_ps(ptr, _mm_mul_ps(_mm_loadu_ps(ptr), gain));
+                    }
+                    if channels % 4 == 2 {
+                        let ptr = frame.as_mut_ptr().add(channels - 2).cast();
+                        let pair = _mm_castpd_ps(_mm_load_sd(ptr));
+                        _mm_store_sd(ptr, _mm_castps_pd(_mm_mul_ps(pair, gain)));
+                    }
+                }
+                buf.len() / channels * channels
+            } else {
+                0
+            }
+        }
+    }
+
+    pub(super) fn fold_to_stereo(from: &[f32], gains: &[[f32; 2]], to: &mut [(f32, f32)]) -> usize {
+        let channels = gains.len();
+        if ![4, 6, 8].contains(&channels) {
+            return 0;
+        }
+        let frames = (from.len() / channels).min(to.len()) & !3;
+        let (left, right) = padded_gains(gains);
+        // SAFETY: SSE2 is part of x86_64, the gains are arrays of eight elements, and every load
+        // of a frame is within the first `frames` frames of `from`.
+        unsafe {
+            let gain = |gains: &[f32; 8]| {
+                (
+                    _mm_loadu_ps(gains.as_ptr()),
+                    _mm_loadu_ps(gains.as_ptr().add(4)),
+                )
+            };
+            let (left, right) = (gain(&left), gain(&right));
+            // Channels 0 to 3, and whichever of channels 4 to 7 there are.
+            let load = |frame: *const f32| {
+                let hi = match channels {
+                    4 => _mm_setzero_ps(),
+                    6 => _mm_castpd_ps(_mm_load_sd(frame.add(4).cast())),
+                    _ => _mm_loadu_ps(frame.add(4)),
+                };
+                (_mm_loadu_ps(frame), hi)
+            };
+            let dot = |(lo, hi): (__m128, __m128), (gain_lo, gain_hi): (__m128, __m128)| {
+                _mm_add_ps(_mm_mul_ps(lo, gain_lo), _mm_mul_ps(hi, gain_hi))
+            };
+            // Sums the lanes of each of four vectors into a lane of one.
+            let sum = |v: [__m128; 4]| {
+                let a = _mm_add_ps(_mm_unpacklo_ps(v[0], v[1]), _mm_unpackhi_ps(v[0], v[1]));
+                let b = _mm_add_ps(_mm_unpacklo_ps(v[2], v[3]), _mm_unpackhi_ps(v[2], v[3]));
+                _mm_add_ps(_mm_movelh_ps(a, b), _mm_movehl_ps(b, a))
+            };
+            let mut sums = [[0.0; 4]; 2];
+            for i in (0..frames).step_by(4) {
+                let frame = |k: usize| load(from.as_ptr().add((i + k) * channels));
+                let frames = [frame(0), frame(1), frame(2), frame(3)];
+                _mm_storeu_ps(sums[0].as_mut_ptr(), sum(frames.map(|f| dot(f, left))));
+                _mm_storeu_ps(sums[1].as_mut_ptr(), sum(frames.map(|f| dot(f, right))));
+                for (k, out) in to[i..i + 4].iter_mut().enumerate() {
+                    *out = (sums[0][k], sums[1][k]);
+                }
+            }
+        }
+        frames
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arch {
+    use std::arch::aarch64::*;
+
+    use super::{lane_frames, padded_gains};
+
+    pub(super) fn f32_to_i16(src: &[f32], dst: &mut [i16]) -> usize {
+        let len = src.len() & !7;
+        // SAFETY: NEON is part of aarch64, and every load and store is within the first `len`
+        // elements of both slices.
+        unsafe {
+            let (min, max) = (vdupq_n_f32(-1.0), vdupq_n_f32(1.0));
+            let scale = vdupq_n_f32(i16::MAX as f32);
+            // Converting NaN gives zero, as the scalar code does.
+            let convert = |ptr: *const f32| {
+                let x = vmaxq_f32(vminq_f32(vld1q_f32(ptr), max), min);
+                vqmovn_s32(vcvtq_s32_f32(vmulq_f32(x, scale)))
+            };
+            for i in (0..len).step_by(8) {
+                let lo = convert(src.as_ptr().add(i));
+                let hi = convert(src.as_ptr().add(i + 4));
+                vst1q_s16(dst.as_mut_ptr().add(i), vcombine_s16(lo, hi));
+            }
+        }
+        len
+    }
+
+    // `is_multiple_of` would raise the MSRV to 1.87.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub(super) fn ramp_gain(buf: &mut [f32], channels: usize, start: f32, step: f32) -> usize {
+        // SAFETY: NEON is part of aarch64, and every load and store is within `buf`.
+        unsafe {
+            let (vstart, vstep) = (vdupq_n_f32(start), vdupq_n_f32(step));
+            if let Some(lanes) = lane_frames(channels) {
+                let lanes = vld1q_f32(lanes.as_ptr());
+                let len = buf.len() & !3;
+                for i in (0..len).step_by(4) {
+                    let frame = vaddq_f32(vdupq_n_f32((i / channels) as f32), lanes);
+                    let gain = vaddq_f32(vstart, vmulq_f32(vstep, frame));
+                    let ptr = buf.as_mut_ptr().add(i);
+                    vst1q_f32(ptr, vmulq_f32(vld1q_f32(ptr), gain));
+                }
+                len
+            } else if channels % 2 == 0 {
+                for (i, frame) in buf.chunks_exact_mut(channels).enumerate() {
+                    let gain = vdupq_n_f32(start + step * i as f32);
+                    for j in (0..channels & !3).step_by(4) {
+                        let ptr = frame.as_mut_ptr().add(j);
+                        vst1q_f32(ptr, vmulq_f32(vld1q_f32(ptr), gain));
+                    }
+                    if channels % 4 == 2 {
+                        let ptr = frame.as_mut_ptr().add(channels - 2);
+                        vst1_f32(ptr, vmul_f32(vld1_f32(ptr), vget_low_f32(gain)));
+                    }
+                }
+                buf.len() / channels * channels
+            } else {
+                0
+            }
+        }
+    }
+
+    pub(super) fn fold_to_stereo(from: &[f32], gains: &[[f32; 2]], to: &mut [(f32, f32)]) -> usize {
+        let channels = gains.len();
+        if !(4..=8).contains(&channels) {
+            return 0;
+        }
+        let (left, right) = padded_gains(gains);
+        let mut padded = [0.0; 8];
+        let mut done = 0;
+        // SAFETY: NEON is part of aarch64, and every load is from an array of eight elements.
+        unsafe {
+            let (left_lo, left_hi) = (vld1q_f32(left.as_ptr()), vld1q_f32(left.as_ptr().add(4)));
+            let (right_lo, right_hi) =
+                (vld1q_f32(right.as_ptr()), vld1q_f32(right.as_ptr().add(4)));
+            for (frame, out) in from.chunks_exact(channels).zip(to) {
+                padded[..channels].copy_from_slice(frame);
+                let lo = vld1q_f32(padded.as_ptr());
+                let hi = vld1q_f32(padded.as_ptr().add(4));
+                let l = vaddq_f32(vmulq_f32(lo, left_lo), vmulq_f32(hi, left_hi));
+                let r = vaddq_f32(vmulq_f32(lo, right_lo), vmulq_f32(hi, right_hi));
+                *out = (vaddvq_f32(l), vaddvq_f32(r));
+                done += 1;
+            }
+        }
+        done
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod arch {
+    pub(super) fn f32_to_i16(_: &[f32], _: &mut [i16]) -> usize {
+        0
+    }
+
+    pub(super) fn ramp_gain(_: &mut [f32], _: usize, _: f32, _: f32) -> usize {
+        0
+    }
+
+    pub(super) fn fold_to_stereo(_: &[f32], _: &[[f32; 2]], _: &mut [(f32, f32)]) -> usize {
+        0
+    }
+}
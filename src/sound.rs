@@ -0,0 +1,1029 @@
+//! A playback device together with the engine driving it.
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    mem,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Receiver,
+        Arc, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use rg3d_sound::{context::SoundContext, hrtf::HrirSphere};
+use sdl2::{
+    audio::{AudioDevice, AudioStatus},
+    event::{Event, WindowEvent},
+    AudioSubsystem,
+};
+
+use crate::{
+    crossfade::Handoff,
+    driver::{self, Device},
+    event::{self, AudioEvent, EventSender},
+    null::NullClock,
+    rate::RateMonitor,
+    telemetry,
+    watchdog::Watchdog,
+    AudioConfig, Callback, DeviceSelector, EngineHandle, EngineId, InjectionMode, ObtainedSpec,
+    OpenOptions, RateChangePolicy, ReconnectPolicy, TestSignal,
+};
+
+/// How long the output takes to fade out when the game window loses focus, and back in when it
+/// regains it.
+const FOCUS_FADE: Duration = Duration::from_millis(300);
+
+/// An open SDL playback device, together with the [`SoundEngine`][rg3d_sound::engine::SoundEngine]
+/// which drives it. Created with [`OpenOptions::open`][crate::OpenOptions::open].
+///
+/// Call [`SdlSound::update`] regularly, for example once per frame, so that lost devices are
+/// noticed and reported through [`SdlSound::events`].
+pub struct SdlSound {
+    subsystem: AudioSubsystem,
+    /// The options the device was opened with.
+    options: OpenOptions,
+    engine: EngineHandle,
+    device: Device,
+    /// The name of the device, or `None` for the default device.
+    name: Option<String>,
+    sender: EventSender,
+    events: Receiver<AudioEvent>,
+    resync: Arc<AtomicBool>,
+    /// The status of the device when last checked, so that changes can be reported.
+    status: Cell<AudioStatus>,
+    /// Whether the device was playing when last checked, so it can be resumed after reconnecting.
+    playing: bool,
+    lost: bool,
+    /// The number of the next attempt to reopen the lost device, and when to make it.
+    retry: Option<(u32, Instant)>,
+    hrtf: bool,
+    /// The HRIR sphere file HRTF was enabled with by an [`AudioConfig`], with `None` for the
+    /// bundled sphere, so that a config changing it can be noticed.
+    config_hrir: Option<Option<PathBuf>>,
+    /// The output trim of each device, keyed by name, with `None` for the default device.
+    trims: HashMap<Option<String>, f32>,
+    watchdog: Option<Watchdog>,
+    /// A device being switched away from, and when it will have faded out.
+    fading_out: Option<(Device, Instant)>,
+    rate_monitor: Option<RateMonitor>,
+    /// Runs the callback while the device is lost, with [`OpenOptions::null_fallback`].
+    null_clock: Option<NullClock>,
+    /// The contexts paused by [`SdlSound::pause_all`], to resume with [`SdlSound::resume_all`].
+    frozen: Vec<SoundContext>,
+    mute_on_focus_loss: bool,
+    /// Whether the game window has focus, as of the last focus event.
+    focused: bool,
+}
+
+impl SdlSound {
+    /// Wraps a newly opened device, whose callback sends its events to `sender`, and announces
+    /// that it was opened.
+    pub(crate) fn new(
+        subsystem: AudioSubsystem,
+        options: OpenOptions,
+        engine: EngineHandle,
+        mut device: AudioDevice<Callback>,
+        name: Option<String>,
+        (sender, events): (EventSender, Receiver<AudioEvent>),
+    ) -> Self {
+        let resync = device.lock().resync_flag();
+        let period = ObtainedSpec::from(*device.spec()).buffer_duration();
+        let watchdog = options.watchdog.map(|policy| {
+            Watchdog::spawn(
+                policy,
+                device.lock().heartbeat(),
+                period,
+                name.clone(),
+                sender.clone(),
+            )
+        });
+        let rate_monitor = options.rate_change.map(|policy| {
+            let heartbeat = device.lock().heartbeat();
+            RateMonitor::new(policy, heartbeat, device.spec())
+        });
+        let sound = Self {
+            subsystem,
+            options,
+            engine,
+            device: Device::new(device),
+            name,
+            sender,
+            events,
+            resync,
+            status: Cell::new(AudioStatus::Paused),
+            playing: false,
+            lost: false,
+            retry: None,
+            hrtf: false,
+            config_hrir: None,
+            trims: HashMap::new(),
+            watchdog,
+            fading_out: None,
+            rate_monitor,
+            frozen: Vec::new(),
+            null_clock: None,
+            mute_on_focus_loss: false,
+            focused: true,
+        };
+        telemetry::device_opened(sound.obtained_spec().latency());
+        sound.send(AudioEvent::DeviceOpened {
+            device: sound.name.clone(),
+            spec: sound.obtained_spec(),
+        });
+        sound
+    }
+
+    /// Opens a device with the given settings, as with [`AudioConfig::open_options`], and applies
+    /// the rest of the settings to its engine. On error, returns the SDL error, or a description of
+    /// the setting which couldn't be applied.
+    pub fn from_config(subsystem: &AudioSubsystem, config: &AudioConfig) -> Result<Self, String> {
+        let mut sound = config.open_options().open(subsystem)?;
+        config.apply_to_engine(&mut sound)?;
+        Ok(sound)
+    }
+
+    pub(crate) fn send(&self, event: AudioEvent) {
+        event::send(&self.sender, event);
+    }
+
+    /// Returns the receiver of events about the device.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::AudioEvent;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.resume();
+    ///
+    /// // In the game loop:
+    /// sound.update();
+    /// for event in sound.events().try_iter() {
+    ///     match event {
+    ///         AudioEvent::DeviceLost { .. } => eprintln!("Audio device lost"),
+    ///         AudioEvent::Underrun { gap } => eprintln!("Audio glitched for {:?}", gap),
+    ///         _ => {}
+    ///     }
+    /// }
+    /// ```
+    pub fn events(&self) -> &Receiver<AudioEvent> {
+        &self.events
+    }
+
+    /// Returns a [`Stream`][futures::Stream] of the events sent from now on, alongside
+    /// [`SdlSound::events`]. See [`AudioEvents`][crate::AudioEvents].
+    #[cfg(feature = "async")]
+    pub fn event_stream(&self) -> crate::AudioEvents {
+        self.sender.streams.subscribe()
+    }
+
+    /// Checks on the state of the device, sending [`AudioEvent::StatusChanged`] if it started or
+    /// stopped playing other than through [`SdlSound::resume`] or [`SdlSound::pause`], for example
+    /// because the OS suspended audio, and [`AudioEvent::DeviceLost`] if it has been lost since the
+    /// last update. If a [`ReconnectPolicy`] is set, this also makes any attempts to
+    /// reopen a lost device that are due, and it closes devices which have been switched away
+    /// from once they have faded out.
+    pub fn update(&mut self) {
+        if self
+            .fading_out
+            .as_ref()
+            .is_some_and(|(_, until)| Instant::now() >= *until)
+        {
+            self.fading_out = None;
+        }
+        self.check_status();
+        if !self.lost {
+            match self.device.status() {
+                AudioStatus::Stopped => self.device_lost(),
+                status => self.playing = status == AudioStatus::Playing,
+            }
+        }
+        if let Some((attempt, at)) = self.retry {
+            if Instant::now() >= at {
+                self.reconnect(attempt);
+            }
+        }
+        self.check_rate();
+        self.tick_null_clock();
+        let reopen = self
+            .watchdog
+            .as_ref()
+            .is_some_and(|watchdog| watchdog.take_stalled() && watchdog.policy().reopen);
+        if reopen {
+            match self.reopen(self.options.clone()) {
+                Ok(()) => self.send(AudioEvent::Reconnected {
+                    device: self.name.clone(),
+                }),
+                Err(_) => self.device_lost(),
+            }
+        }
+    }
+
+    /// Sets how to try reopening the device if it is lost, or with `None`, stops trying.
+    pub fn set_reconnect(&mut self, policy: impl Into<Option<ReconnectPolicy>>) {
+        self.options.reconnect = policy.into();
+        self.retry = match self.options.reconnect {
+            Some(policy) if self.lost => Some((1, Instant::now() + policy.delay(1))),
+            _ => None,
+        };
+    }
+
+    /// Returns whether the engines are being run without a device, as the device was lost while
+    /// playing with [`OpenOptions::null_fallback`].
+    pub fn is_null_output(&self) -> bool {
+        self.null_clock.is_some()
+    }
+
+    fn tick_null_clock(&mut self) {
+        if !(self.lost && self.playing && self.options.null_fallback) {
+            self.null_clock = None;
+            return;
+        }
+        let spec = self.obtained_spec();
+        let clock = self.null_clock.get_or_insert_with(|| {
+            // The gap since the device was lost isn't an underrun.
+            self.resync.store(true, Ordering::Relaxed);
+            NullClock::new()
+        });
+        clock.tick(&mut self.device.lock(), spec);
+    }
+
+    /// Measures the rate the device is playing at, and handles it changing.
+    fn check_rate(&mut self) {
+        let Some(monitor) = &mut self.rate_monitor else {
+            return;
+        };
+        if !self.playing || self.lost {
+            monitor.restart(None);
+            return;
+        }
+        let Some(rate) = monitor.check(Instant::now()) else {
+            return;
+        };
+        let policy = monitor.policy();
+        self.send(AudioEvent::SampleRateChanged {
+            device: self.name.clone(),
+            rate,
+        });
+        match policy {
+            RateChangePolicy::Resample => self.device.lock().set_device_rate(rate),
+            RateChangePolicy::Reopen => {
+                if self.reopen(self.options.clone()).is_err() {
+                    self.device_lost();
+                }
+            }
+        }
+    }
+
+    fn device_lost(&mut self) {
+        self.lost = true;
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.set_playing(false);
+        }
+        self.send(AudioEvent::DeviceLost {
+            device: self.name.clone(),
+        });
+        if let Some(policy) = self.options.reconnect {
+            self.schedule_retry(1, &policy);
+        }
+    }
+
+    fn schedule_retry(&mut self, attempt: u32, policy: &ReconnectPolicy) {
+        let retry_in = policy.delay(attempt);
+        self.retry = Some((attempt, Instant::now() + retry_in));
+        self.send(AudioEvent::Reconnecting { attempt, retry_in });
+    }
+
+    /// Tries to reopen the lost device, then the default device, scheduling another attempt if
+    /// both fail.
+    fn reconnect(&mut self, attempt: u32) {
+        let policy = match self.options.reconnect {
+            Some(policy) => policy,
+            None => return,
+        };
+        let mut selectors = vec![self.options.device.clone()];
+        if self.options.device != DeviceSelector::Default {
+            selectors.push(DeviceSelector::Default);
+        }
+        for selector in selectors {
+            let options = self.options.clone().device(selector).clone();
+            if let Ok((from, _)) = self.replace_device(&options, Duration::ZERO) {
+                self.lost = false;
+                self.retry = None;
+                telemetry::reconnected();
+                telemetry::device_opened(self.obtained_spec().latency());
+                self.send(AudioEvent::Reconnected {
+                    device: self.name.clone(),
+                });
+                if from != self.name {
+                    self.send(AudioEvent::DeviceSwitched {
+                        from,
+                        to: self.name.clone(),
+                    });
+                }
+                if self.playing {
+                    self.resume();
+                }
+                return;
+            }
+        }
+
+        if policy.max_attempts.is_some_and(|max| attempt >= max) {
+            self.retry = None;
+            self.send(AudioEvent::ReconnectFailed { attempts: attempt });
+        } else {
+            self.schedule_retry(attempt + 1, &policy);
+        }
+    }
+
+    /// Replaces the device with one opened with new options, for example to change the latency
+    /// or channel layout from a settings menu. The engine, and so every context and how far
+    /// through each source is, stays attached, as does everything else set on the callback, and
+    /// the new device is playing if the old one was. The underrun fill and gain ramp set on the
+    /// callback are only replaced if `options` changes them from the current options.
+    ///
+    /// The new device is opened before the old one is closed, so drivers which can only open one
+    /// device at a time, such as the dummy driver, can't be reopened, unless the new device needs
+    /// another driver, in which case the old one must be closed first. On error, returns the SDL
+    /// error, or a description of why the obtained spec was rejected, and the old device is kept,
+    /// or reopened if it had to be closed.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::{ChannelLayout, OpenOptions};
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = OpenOptions::new().start_paused(false).open(&audio).unwrap();
+    ///
+    /// // The player switched to a surround sound system in the settings menu.
+    /// let options = sound.options().clone().channel_layout(ChannelLayout::Surround51).clone();
+    /// sound.reopen(options).unwrap();
+    /// ```
+    pub fn reopen(&mut self, options: OpenOptions) -> Result<(), String> {
+        self.reopen_with_crossfade(options, Duration::ZERO)
+    }
+
+    /// Switches playback to another device, crossfading from the old device to the new one over
+    /// `crossfade` so that there's no hard cut. Both devices play at once while the old one fades
+    /// out a copy of the output, and the old one is closed by [`SdlSound::update`] once it has
+    /// faded out. If the device is paused, or the new device needs another driver, so that the old
+    /// one must be closed first, it is switched straight away. On error, returns the SDL error, and
+    /// the old device is kept, or reopened if it had to be closed.
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use rg3d_sound_sdl::DeviceSelector;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.resume();
+    ///
+    /// // The player picked their headphones in the settings menu.
+    /// sound
+    ///     .switch_device(DeviceSelector::Index(1), Duration::from_millis(300))
+    ///     .unwrap();
+    ///
+    /// // In the game loop:
+    /// sound.update();
+    /// ```
+    pub fn switch_device(
+        &mut self,
+        device: DeviceSelector,
+        crossfade: Duration,
+    ) -> Result<(), String> {
+        let options = self.options.clone().device(device).clone();
+        self.reopen_with_crossfade(options, crossfade)
+    }
+
+    /// Like [`SdlSound::switch_device`], but completes once the old device has faded out and been
+    /// closed, rather than leaving it to [`SdlSound::update`]. The wait runs on a thread of its
+    /// own, so any executor can drive the future.
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use rg3d_sound_sdl::DeviceSelector;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.resume();
+    ///
+    /// futures::executor::block_on(
+    ///     sound.switch_device_async(DeviceSelector::Index(1), Duration::from_millis(300)),
+    /// )
+    /// .unwrap();
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn switch_device_async(
+        &mut self,
+        device: DeviceSelector,
+        crossfade: Duration,
+    ) -> Result<(), String> {
+        self.switch_device(device, crossfade)?;
+        if let Some(&(_, until)) = self.fading_out.as_ref() {
+            crate::asynchronous::Sleep::until(until).await;
+            self.fading_out = None;
+        }
+        Ok(())
+    }
+
+    fn reopen_with_crossfade(
+        &mut self,
+        options: OpenOptions,
+        crossfade: Duration,
+    ) -> Result<(), String> {
+        let playing = self.device.status() == AudioStatus::Playing;
+        let crossfade = if playing { crossfade } else { Duration::ZERO };
+        let spec = self.obtained_spec();
+        let (from, old) = self.replace_device(&options, crossfade)?;
+        if !crossfade.is_zero() && !old.is_closed() {
+            // Allow for whatever the old device had buffered as well.
+            let until = Instant::now() + crossfade + spec.buffer_duration() * 2;
+            self.fading_out = Some((old, until));
+        }
+        self.options = options;
+        self.lost = false;
+        self.retry = None;
+        telemetry::device_opened(self.obtained_spec().latency());
+        if from != self.name {
+            self.send(AudioEvent::DeviceSwitched {
+                from,
+                to: self.name.clone(),
+            });
+        }
+        if self.obtained_spec() != spec {
+            self.send(AudioEvent::SpecChanged {
+                spec: self.obtained_spec(),
+            });
+        }
+        if playing {
+            self.resume();
+        }
+        Ok(())
+    }
+
+    /// Opens a new device with the given options and moves the callback, with everything attached
+    /// to it, over from the current device. The new device is left paused. Returns the name of the
+    /// old device, and the old device itself, which is left fading out a copy of the output over
+    /// `crossfade`, or silent if that's zero.
+    ///
+    /// If the new device needs another driver, the current device is closed first, as switching
+    /// drivers closes every device, and the old device returned is closed. If the new device then
+    /// can't be opened, the old one is reopened on the driver it was switched from.
+    fn replace_device(
+        &mut self,
+        options: &OpenOptions,
+        crossfade: Duration,
+    ) -> Result<(Option<String>, Device), String> {
+        let playing = self.device.status() == AudioStatus::Playing;
+        let previous = driver::current();
+        let switch = options.device.required_driver(options.driver.as_deref());
+        if let Some(driver) = &switch {
+            driver::check_can_switch(driver, 1 + usize::from(self.fading_out.is_some()))?;
+            self.fading_out = None;
+            let switched = driver::init_driver(driver, 1);
+            self.device.mark_closed();
+            if let Err(e) = switched {
+                self.restore_driver(previous, playing);
+                return Err(e);
+            }
+        }
+        let crossfade = if self.device.is_closed() {
+            Duration::ZERO
+        } else {
+            crossfade
+        };
+        // A closed device can only be locked until another is opened, so its callback is taken
+        // off it first. Otherwise, the new device is opened before the old one is closed, so that
+        // a failure leaves things as they were.
+        let mut parked = self.device.is_closed().then(|| self.device.take_callback());
+        let (mut device, name) =
+            match options.open_device(&self.subsystem, &self.engine, &self.sender) {
+                Ok(opened) => opened,
+                Err(e) => {
+                    if let Some(callback) = parked {
+                        self.device.put_callback(callback);
+                    }
+                    if switch.is_some() {
+                        self.restore_driver(previous, playing);
+                    }
+                    return Err(e);
+                }
+            };
+        let trim = self.device_trim(name.as_deref()).unwrap_or(1.0);
+        let rate = device.spec().freq as u32;
+        {
+            let mut new = device.lock();
+            let mut guard;
+            let old = match &mut parked {
+                Some(callback) => callback,
+                None => {
+                    guard = self.device.lock();
+                    &mut *guard
+                }
+            };
+            move_callback(&mut new, old, trim, rate, &self.options, options, crossfade);
+        }
+        if let Some(monitor) = &mut self.rate_monitor {
+            monitor.restart(Some(device.spec()));
+        }
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.watch(
+                ObtainedSpec::from(*device.spec()).buffer_duration(),
+                name.clone(),
+            );
+        }
+        let old = mem::replace(&mut self.device, Device::new(device));
+        Ok((mem::replace(&mut self.name, name), old))
+    }
+
+    /// Reopens the device, closed by failing to switch from `driver`, on that driver with the
+    /// options it was opened with. If that fails too, the device is left closed, to be noticed as
+    /// lost, and if SDL was left without a driver at all, it is given the dummy driver.
+    fn restore_driver(&mut self, driver: Option<&str>, playing: bool) {
+        let mut options = self.options.clone();
+        if let Some(driver) = driver {
+            options.driver(driver);
+        }
+        let restored = match driver {
+            Some(driver) => driver::init_driver(driver, 0)
+                .and_then(|_| self.replace_device(&options, Duration::ZERO)),
+            None => Err(String::new()),
+        };
+        match restored {
+            Ok(_) if playing => self.resume(),
+            Ok(_) => {}
+            Err(_) => {
+                if driver::current().is_none() {
+                    let _ = driver::init_driver("dummy", 0);
+                }
+            }
+        }
+    }
+
+    /// Sets the output trim of a device, given by name or `None` for the default device, to make
+    /// up for differences in loudness between, for example, a TV and headphones. The trim is a
+    /// gain applied on top of the engine's master gain, and is applied whenever playback switches
+    /// or reconnects to that device, including straight away if it's the current device.
+    /// # Example
+    /// ```no_run
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.set_device_trim("USB Headset", 0.5);
+    /// sound.set_device_trim(None, 1.2);
+    /// ```
+    pub fn set_device_trim<'a>(&mut self, device: impl Into<Option<&'a str>>, gain: f32) {
+        let device = device.into();
+        if device == self.name.as_deref() {
+            self.device.lock().set_output_trim(gain);
+        }
+        self.trims.insert(device.map(str::to_string), gain);
+    }
+
+    /// Returns the output trim of a device, or `None` if none has been set.
+    pub fn device_trim<'a>(&self, device: impl Into<Option<&'a str>>) -> Option<f32> {
+        let device = device.into().map(str::to_string);
+        self.trims.get(&device).copied()
+    }
+
+    /// Forgets the output trim of a device. If it's the current device, its trim returns to 1.
+    pub fn remove_device_trim<'a>(&mut self, device: impl Into<Option<&'a str>>) {
+        let device = device.into();
+        if device == self.name.as_deref() {
+            self.device.lock().set_output_trim(1.0);
+        }
+        self.trims.remove(&device.map(str::to_string));
+    }
+
+    /// Sets the balance between the left and right speakers, from -1 for fully left to 1 for fully
+    /// right, as with [`Callback::set_balance`], for example from an "L/R balance" slider.
+    /// # Example
+    /// ```no_run
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.set_balance(-0.25);
+    /// ```
+    pub fn set_balance(&mut self, balance: f32) {
+        self.device.lock().set_balance(balance);
+    }
+
+    /// Sets the master gain of the output, as with [`Callback::set_master_gain`], ramping to it
+    /// so that a volume slider doesn't crackle.
+    /// # Example
+    /// ```no_run
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.set_master_gain(0.5);
+    /// ```
+    pub fn set_master_gain(&mut self, gain: f32) {
+        self.device.lock().set_master_gain(gain);
+    }
+
+    /// Returns the master gain of the output.
+    pub fn master_gain(&mut self) -> f32 {
+        self.device.lock().master_gain()
+    }
+
+    /// Sets whether the left and right speakers both play the same mono mix, as with
+    /// [`Callback::set_mono_downmix`], without reopening the device.
+    pub fn set_mono_downmix(&mut self, mono: bool) {
+        self.device.lock().set_mono_downmix(mono);
+    }
+
+    /// Sets whether the left and right channels are swapped, as with [`Callback::swap_channels`].
+    pub fn swap_channels(&mut self, swapped: bool) {
+        self.device.lock().swap_channels(swapped);
+    }
+
+    /// Returns the name of the device, or `None` if it was opened without a name, as the default
+    /// device is.
+    pub fn device_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns whether the device was opened without a name, leaving SDL to pick the default
+    /// device, rather than one chosen by name. SDL has no way to tell whether a named device is
+    /// the default one, so a device opened by the name of the default device isn't counted.
+    /// # Example
+    /// ```no_run
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    ///
+    /// // In the settings menu:
+    /// if sound.is_opened_without_name() {
+    ///     println!("Output: system default");
+    /// }
+    /// ```
+    pub fn is_opened_without_name(&self) -> bool {
+        self.name.is_none()
+    }
+
+    /// Returns the name of the SDL audio driver the device was opened with, such as
+    /// `"pulseaudio"` or `"wasapi"`.
+    /// # Example
+    /// ```no_run
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    ///
+    /// // In a support overlay:
+    /// println!(
+    ///     "{} on {}, {} frames ({:.1}ms)",
+    ///     sound.device_name().unwrap_or("unnamed device"),
+    ///     sound.driver(),
+    ///     sound.buffer_frames(),
+    ///     sound.buffer_duration().as_secs_f64() * 1000.0
+    /// );
+    /// ```
+    pub fn driver(&self) -> &'static str {
+        self.subsystem.current_audio_driver()
+    }
+
+    /// Returns the size of the device's buffer, in frames.
+    pub fn buffer_frames(&self) -> u16 {
+        self.device.spec().samples
+    }
+
+    /// Returns how long the device's buffer takes to play, as with
+    /// [`ObtainedSpec::buffer_duration`].
+    pub fn buffer_duration(&self) -> Duration {
+        self.obtained_spec().buffer_duration()
+    }
+
+    /// Returns the options the device was opened with, as changed by switching or reopening it.
+    pub fn options(&self) -> &OpenOptions {
+        &self.options
+    }
+
+    /// Returns the handle of the engine driving the device.
+    pub fn engine(&self) -> &EngineHandle {
+        &self.engine
+    }
+
+    /// Replaces the engine driving the device with another, crossfading between them over
+    /// `crossfade` so that the device never stops playing, for example when loading a saved game.
+    /// The old engine keeps rendering until it has faded out, unless the device is paused, in
+    /// which case it is replaced straight away. Nothing is copied from the old engine, so call
+    /// [`SdlSound::set_hrtf`] again if the new one should use HRTF.
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.resume();
+    ///
+    /// let loaded = rg3d_sound_sdl::new_engine();
+    /// sound.replace_engine(loaded, Duration::from_millis(150));
+    /// ```
+    pub fn replace_engine(&mut self, engine: EngineHandle, crossfade: Duration) {
+        let crossfade = if self.is_paused() {
+            Duration::ZERO
+        } else {
+            crossfade
+        };
+        self.device
+            .lock()
+            .replace_engine(EngineId::MAIN, Arc::clone(&engine), crossfade);
+        self.engine = engine;
+        self.hrtf = false;
+        self.config_hrir = None;
+    }
+
+    /// Returns the spec SDL obtained for the device.
+    pub fn obtained_spec(&self) -> ObtainedSpec {
+        (*self.device.spec()).into()
+    }
+
+    /// Returns the SDL device.
+    pub fn device(&self) -> &AudioDevice<Callback> {
+        &self.device
+    }
+
+    /// Returns the SDL device mutably, for example to [lock][AudioDevice::lock] it.
+    pub fn device_mut(&mut self) -> &mut AudioDevice<Callback> {
+        &mut self.device
+    }
+
+    /// Locks the device and calls `f` with its callback, so that the callback can be
+    /// reconfigured while the device plays. The audio thread waits while `f` runs, so it should
+    /// return quickly.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound_sdl::EngineId;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.resume();
+    ///
+    /// let media = sound.with_callback(|callback| {
+    ///     callback.set_gain(EngineId::MAIN, 0.5);
+    ///     callback.attach(rg3d_sound_sdl::new_engine(), 0.8)
+    /// });
+    /// ```
+    pub fn with_callback<T>(&mut self, f: impl FnOnce(&mut Callback) -> T) -> T {
+        f(&mut self.device.lock())
+    }
+
+    /// Splits this into the engine handle and the SDL device, as returned by [`open`][crate::open].
+    pub fn into_parts(self) -> (EngineHandle, AudioDevice<Callback>) {
+        (self.engine, self.device.into_inner())
+    }
+
+    /// Starts playback.
+    pub fn resume(&self) {
+        self.resync.store(true, Ordering::Relaxed);
+        self.device.resume();
+        self.check_status();
+    }
+
+    /// Pauses playback. The engine isn't rendered while the device is paused.
+    pub fn pause(&self) {
+        self.device.pause();
+        self.check_status();
+    }
+
+    /// Pauses the device and every context of every engine attached to its callback together, for
+    /// pausing the game. The device is paused first, so the callback can't run while the contexts
+    /// are paused, and so when [`SdlSound::resume_all`] resumes them, every source carries on from
+    /// exactly where it was. Contexts which were already paused are left alone, and stay paused
+    /// after resuming.
+    /// # Example
+    /// ```no_run
+    /// use rg3d_sound::context::SoundContext;
+    ///
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.engine().lock().unwrap().add_context(SoundContext::new());
+    /// sound.resume();
+    ///
+    /// // The player opened the pause menu.
+    /// sound.pause_all();
+    /// // And closed it again.
+    /// sound.resume_all();
+    /// ```
+    pub fn pause_all(&mut self) {
+        self.pause();
+        let engines = self.with_callback(|callback| {
+            let ids: Vec<_> = callback.engine_ids().collect();
+            ids.into_iter()
+                .filter_map(|id| callback.engine(id).cloned())
+                .collect::<Vec<_>>()
+        });
+        for engine in engines {
+            let engine = engine.lock().unwrap_or_else(PoisonError::into_inner);
+            for context in engine.contexts() {
+                let mut state = context.state();
+                if !state.is_paused() && !self.frozen.contains(context) {
+                    state.pause(true);
+                    self.frozen.push(context.clone());
+                }
+            }
+        }
+    }
+
+    /// Resumes the contexts paused by [`SdlSound::pause_all`], then the device.
+    pub fn resume_all(&mut self) {
+        for context in self.frozen.drain(..) {
+            context.state().pause(false);
+        }
+        self.resume();
+    }
+
+    /// Returns whether the device is playing.
+    pub fn is_playing(&self) -> bool {
+        self.device.status() == AudioStatus::Playing
+    }
+
+    /// Returns whether playback is paused.
+    pub fn is_paused(&self) -> bool {
+        self.device.status() == AudioStatus::Paused
+    }
+
+    /// Sends [`AudioEvent::StatusChanged`] if the status of the device changed since it was last
+    /// checked.
+    fn check_status(&self) {
+        let status = self.device.status();
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.set_playing(status == AudioStatus::Playing);
+        }
+        if self.status.replace(status) != status {
+            self.send(AudioEvent::StatusChanged { status });
+        }
+    }
+
+    /// Switches every attached context between HRTF rendering with the given sphere, and the
+    /// default panning renderer with `None`, for example from a "headphone mode" setting. While
+    /// playing, the switch is crossfaded as with [`Callback::set_hrtf`]; while paused, it happens
+    /// straight away.
+    pub fn set_hrtf(&mut self, sphere: Option<HrirSphere>) {
+        self.hrtf = sphere.is_some();
+        self.config_hrir = None;
+        let paused = self.is_paused();
+        let mut callback = self.device.lock();
+        callback.set_hrtf(sphere);
+        if paused {
+            callback.finish_fade();
+        }
+    }
+
+    /// Returns whether contexts are being rendered with HRTF, as set with [`SdlSound::set_hrtf`].
+    pub fn is_hrtf_enabled(&self) -> bool {
+        self.hrtf
+    }
+
+    /// Returns the HRIR sphere file HRTF was enabled with by an [`AudioConfig`], if it was.
+    pub(crate) fn config_hrir(&self) -> Option<&Option<PathBuf>> {
+        self.config_hrir.as_ref()
+    }
+
+    /// Enables HRTF with a sphere loaded from the file named by an [`AudioConfig`].
+    pub(crate) fn set_config_hrtf(&mut self, sphere: HrirSphere, path: Option<PathBuf>) {
+        self.set_hrtf(Some(sphere));
+        self.config_hrir = Some(path);
+    }
+
+    /// Replaces the output with a sine wave on the given channel, or every channel with `None`, as
+    /// with [`Callback::play_test_tone`].
+    /// # Panics
+    /// This function will panic if `channel` is not a channel of the device.
+    pub fn play_test_tone(
+        &mut self,
+        channel: impl Into<Option<usize>>,
+        frequency: f32,
+        duration: Duration,
+    ) {
+        self.device
+            .lock()
+            .play_test_tone(channel, frequency, duration);
+    }
+
+    /// Sets whether the output fades to silence while the game window doesn't have focus, as
+    /// reported to [`SdlSound::handle_event`], and fades back in once it regains focus. Defaults to
+    /// `false`.
+    /// # Example
+    /// ```no_run
+    /// let sdl = sdl2::init().unwrap();
+    /// let audio = sdl.audio().unwrap();
+    /// let mut sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+    /// sound.set_mute_on_focus_loss(true);
+    ///
+    /// let mut events = sdl.event_pump().unwrap();
+    /// // In the game loop:
+    /// for event in events.poll_iter() {
+    ///     sound.handle_event(&event);
+    /// }
+    /// ```
+    pub fn set_mute_on_focus_loss(&mut self, enabled: bool) {
+        self.mute_on_focus_loss = enabled;
+        self.update_focus_mute();
+    }
+
+    /// Returns whether the output fades to silence while the game window doesn't have focus.
+    pub fn mutes_on_focus_loss(&self) -> bool {
+        self.mute_on_focus_loss
+    }
+
+    /// Handles an SDL event, muting or unmuting the output when the game window loses or gains
+    /// focus, if [enabled][SdlSound::set_mute_on_focus_loss]. Other events are ignored, so every
+    /// event can be passed to this.
+    pub fn handle_event(&mut self, event: &Event) {
+        if let Event::Window { win_event, .. } = event {
+            match win_event {
+                WindowEvent::FocusGained => self.focused = true,
+                WindowEvent::FocusLost => self.focused = false,
+                _ => return,
+            }
+            self.update_focus_mute();
+        }
+    }
+
+    fn update_focus_mute(&mut self) {
+        let muted = self.mute_on_focus_loss && !self.focused;
+        let mut callback = self.device.lock();
+        if callback.is_muted() != muted {
+            callback.set_muted(muted, FOCUS_FADE);
+        }
+    }
+
+    /// Injects a deterministic test signal in place of, or mixed with, the output of the engine,
+    /// or with `None`, stops injecting it, as with [`Callback::set_test_signal`].
+    pub fn set_test_signal(&mut self, signal: impl Into<Option<TestSignal>>, mode: InjectionMode) {
+        let mut callback = self.device.lock();
+        callback.set_test_signal_mode(mode);
+        callback.set_test_signal(signal);
+    }
+
+    /// Loads an HRIR sphere with [`load_hrir_sphere`][crate::load_hrir_sphere] and switches every
+    /// attached context to HRTF rendering with it, as with [`SdlSound::set_hrtf`]. On error,
+    /// returns a description of why the sphere couldn't be loaded, and leaves the renderers alone.
+    #[cfg(feature = "hrir")]
+    pub fn load_hrtf(&mut self, path: impl AsRef<std::path::Path>) -> Result<(), String> {
+        let sphere = crate::load_hrir_sphere(path)?;
+        self.set_hrtf(Some(sphere));
+        Ok(())
+    }
+
+    /// Switches every attached context to HRTF rendering with the
+    /// [bundled sphere][crate::bundled_hrir_sphere], as with [`SdlSound::set_hrtf`].
+    #[cfg(feature = "bundled-hrir")]
+    pub fn enable_bundled_hrtf(&mut self) {
+        self.set_hrtf(Some(crate::bundled_hrir_sphere()));
+    }
+}
+
+/// Moves a callback, with everything attached to it, from `old` to `new`, which has just been
+/// opened with `options` in place of `previous` to play at `rate` to a device with the given trim,
+/// leaving `old` with the callback the device was opened with to fade out a copy of the output over
+/// `crossfade`.
+fn move_callback(
+    new: &mut Callback,
+    old: &mut Callback,
+    trim: f32,
+    rate: u32,
+    previous: &OpenOptions,
+    options: &OpenOptions,
+    crossfade: Duration,
+) {
+    // The callback left behind carries on at the old device's rate.
+    let old_rate = old.device_rate();
+    mem::swap(new, old);
+    old.set_device_rate(old_rate);
+    old.set_output_trim(new.output_trim());
+    old.set_muted(new.is_muted(), Duration::ZERO);
+    // The new device plays at the rate it's opened with.
+    new.set_device_rate(rate);
+    new.set_output_trim(trim);
+    // The callback left behind shares the engine, so it mustn't render it.
+    let handoff = Handoff::new();
+    if !crossfade.is_zero() {
+        new.begin_handoff(handoff.clone(), crossfade);
+    }
+    old.play_handoff(handoff, crossfade);
+    // The callback opened with the device has the layout it was opened with.
+    if new.channel_layout() != old.channel_layout() {
+        new.set_channel_layout(old.channel_layout());
+    }
+    // Settings changed on the callback since are kept, unless the options change them.
+    if options.underrun_fill != previous.underrun_fill {
+        new.set_underrun_fill(options.underrun_fill);
+    }
+    if options.gain_ramp != previous.gain_ramp {
+        new.set_gain_ramp(options.gain_ramp);
+    }
+    // The callback moved to another thread.
+    new.request_realtime(options.realtime);
+}
@@ -0,0 +1,72 @@
+//! The audio spec a playback device was actually opened with.
+
+use std::time::Duration;
+
+use rg3d_sound::engine::SoundEngine;
+use sdl2::audio::{AudioFormat, AudioSpec};
+
+/// The spec SDL obtained for an open playback device, for latency calculations and display.
+/// # Example
+/// ```no_run
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let sound = rg3d_sound_sdl::OpenOptions::new().open(&audio).unwrap();
+/// let spec = sound.obtained_spec();
+/// println!(
+///     "{} Hz, {} frame buffer, ~{} ms latency",
+///     spec.freq,
+///     spec.samples,
+///     spec.latency().as_millis()
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObtainedSpec {
+    /// The sample rate of the audio given to SDL, in Hz.
+    pub freq: i32,
+    /// The format of the samples given to SDL.
+    pub format: AudioFormat,
+    /// The number of interleaved channels.
+    pub channels: u8,
+    /// The silence value SDL calculated for the format.
+    pub silence: u8,
+    /// The size of the device's buffer, in frames.
+    pub samples: u16,
+    /// The size of the device's buffer, in bytes.
+    pub size: u32,
+}
+
+impl ObtainedSpec {
+    /// Returns how long the device's buffer takes to play.
+    pub fn buffer_duration(&self) -> Duration {
+        self.frames_duration(self.samples.into())
+    }
+
+    /// Returns an estimate of the worst case delay between the engine rendering audio and it
+    /// being handed to the driver. When the device's buffer isn't the same size as the blocks the
+    /// engine renders, a whole block may have to be rendered ahead of the buffer being filled.
+    pub fn latency(&self) -> Duration {
+        let block = SoundEngine::render_buffer_len();
+        if self.samples as usize == block {
+            self.buffer_duration()
+        } else {
+            self.buffer_duration() + self.frames_duration(block as u64)
+        }
+    }
+
+    fn frames_duration(&self, frames: u64) -> Duration {
+        Duration::from_secs_f64(frames as f64 / self.freq as f64)
+    }
+}
+
+impl From<AudioSpec> for ObtainedSpec {
+    fn from(spec: AudioSpec) -> Self {
+        Self {
+            freq: spec.freq,
+            format: spec.format,
+            channels: spec.channels,
+            silence: spec.silence,
+            samples: spec.samples,
+            size: spec.size,
+        }
+    }
+}
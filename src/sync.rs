@@ -0,0 +1,47 @@
+//! The lock around each [`SoundEngine`][rg3d_sound::engine::SoundEngine], which is
+//! `parking_lot`'s with the `parking_lot` feature.
+
+use std::sync::{LockResult, TryLockError, TryLockResult};
+
+pub use parking_lot::MutexGuard;
+
+/// A `parking_lot` mutex with the locking API of [`std::sync::Mutex`], so that code written
+/// against [`EngineMutex`][crate::EngineMutex] compiles with or without the `parking_lot`
+/// feature. It's never poisoned, so locking it never fails.
+#[derive(Debug, Default)]
+pub struct Mutex<T: ?Sized>(parking_lot::Mutex<T>);
+
+impl<T> Mutex<T> {
+    /// Creates an unlocked mutex.
+    pub const fn new(value: T) -> Self {
+        Self(parking_lot::const_mutex(value))
+    }
+
+    /// Returns the value inside the mutex. Never fails.
+    pub fn into_inner(self) -> LockResult<T> {
+        Ok(self.0.into_inner())
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Locks the mutex, blocking until it's free. Never fails.
+    pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+        Ok(self.0.lock())
+    }
+
+    /// Locks the mutex if it's free, failing with [`TryLockError::WouldBlock`] if not.
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        self.0.try_lock().ok_or(TryLockError::WouldBlock)
+    }
+
+    /// Returns the value inside the mutex, which can't be locked while it's borrowed mutably.
+    /// Never fails.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        Ok(self.0.get_mut())
+    }
+
+    /// Returns `false`, as the mutex is never poisoned.
+    pub fn is_poisoned(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,68 @@
+//! Publishing audio health through the `metrics` crate, when the `metrics` feature is enabled.
+//!
+//! Without the feature, every function here does nothing.
+
+use std::time::Duration;
+
+#[cfg(feature = "metrics")]
+const CALLBACKS: &str = "rg3d_sound_sdl_callbacks_total";
+#[cfg(feature = "metrics")]
+const UNDERRUNS: &str = "rg3d_sound_sdl_underruns_total";
+#[cfg(feature = "metrics")]
+const RECONNECTS: &str = "rg3d_sound_sdl_reconnects_total";
+#[cfg(feature = "metrics")]
+const DSP_LOAD: &str = "rg3d_sound_sdl_dsp_load";
+#[cfg(feature = "metrics")]
+const LATENCY: &str = "rg3d_sound_sdl_latency_seconds";
+
+/// Describes the metrics published by this crate to the installed `metrics` recorder. Call this
+/// once after installing the recorder, so that exporters can show a description of each metric.
+///
+/// The metrics are:
+/// - `rg3d_sound_sdl_callbacks_total`: the number of buffers played.
+/// - `rg3d_sound_sdl_underruns_total`: the number of [underruns][crate::AudioEvent::Underrun].
+/// - `rg3d_sound_sdl_reconnects_total`: the number of times a lost device was reopened.
+/// - `rg3d_sound_sdl_dsp_load`: the time spent filling the last buffer, as a fraction of the time
+///   it takes to play.
+/// - `rg3d_sound_sdl_latency_seconds`: the latency of the most recently opened device.
+#[cfg(feature = "metrics")]
+pub fn describe_metrics() {
+    use metrics::{describe_counter, describe_gauge, Unit};
+
+    describe_counter!(CALLBACKS, Unit::Count, "Audio buffers played");
+    describe_counter!(UNDERRUNS, Unit::Count, "Audio underruns detected");
+    describe_counter!(RECONNECTS, Unit::Count, "Lost audio devices reopened");
+    describe_gauge!(
+        DSP_LOAD,
+        "Time spent filling the last audio buffer, as a fraction of its duration"
+    );
+    describe_gauge!(LATENCY, Unit::Seconds, "Output latency of the audio device");
+}
+
+/// Records that a buffer was filled, taking `load` of the time it takes to play.
+pub(crate) fn callback_served(load: f64) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!(CALLBACKS).increment(1);
+        metrics::gauge!(DSP_LOAD).set(load);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = load;
+}
+
+pub(crate) fn underrun() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(UNDERRUNS).increment(1);
+}
+
+pub(crate) fn reconnected() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(RECONNECTS).increment(1);
+}
+
+pub(crate) fn device_opened(latency: Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::gauge!(LATENCY).set(latency.as_secs_f64());
+    #[cfg(not(feature = "metrics"))]
+    let _ = latency;
+}
@@ -0,0 +1,180 @@
+//! Test tones which replace the output of a [`Callback`][crate::Callback], for testing speakers,
+//! and deterministic test signals for automated tests of the output chain.
+
+use std::{f32::consts::TAU, time::Duration};
+
+use rg3d_sound::context::SAMPLE_RATE;
+
+/// The level test tones are played at, about -12dBFS.
+const LEVEL: f32 = 0.25;
+/// The number of frames test tones fade in and out over, to avoid clicks.
+const FADE_FRAMES: usize = SAMPLE_RATE as usize / 200;
+
+/// What a test tone sounds like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Signal {
+    /// A sine wave with the given frequency, in Hz.
+    Sine(f32),
+    /// White noise.
+    Noise,
+}
+
+/// A burst of a test signal, played on one or all channels.
+#[derive(Debug, Clone)]
+pub(crate) struct TestTone {
+    signal: Signal,
+    channel: Option<usize>,
+    /// The total length of the burst, and the number of frames already played.
+    len: usize,
+    pos: usize,
+    phase: f32,
+    /// The state of the noise generator.
+    seed: u32,
+}
+
+impl TestTone {
+    pub(crate) fn new(signal: Signal, channel: Option<usize>, duration: Duration) -> Self {
+        Self {
+            signal,
+            channel,
+            len: (duration.as_secs_f64() * SAMPLE_RATE as f64) as usize,
+            pos: 0,
+            phase: 0.0,
+            seed: 0x9e37_79b9,
+        }
+    }
+
+    /// Returns whether the whole burst has been played.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.pos >= self.len
+    }
+
+    /// Overwrites interleaved frames with the next part of the burst. Channels other than the
+    /// selected one are silenced, and any frames after the end of the burst are left alone.
+    pub(crate) fn play(&mut self, frames: &mut [f32], channels: usize) {
+        for frame in frames.chunks_exact_mut(channels) {
+            if self.is_finished() {
+                break;
+            }
+            let fade = self.pos.min(self.len - self.pos).min(FADE_FRAMES) as f32;
+            self.pos += 1;
+            let sample = self.next_sample() * LEVEL * fade / FADE_FRAMES as f32;
+            for (i, out) in frame.iter_mut().enumerate() {
+                *out = match self.channel {
+                    Some(channel) if channel != i => 0.0,
+                    _ => sample,
+                };
+            }
+        }
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        match self.signal {
+            Signal::Sine(frequency) => {
+                let sample = self.phase.sin();
+                self.phase = (self.phase + TAU * frequency / SAMPLE_RATE as f32) % TAU;
+                sample
+            }
+            Signal::Noise => {
+                // xorshift32
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 17;
+                self.seed ^= self.seed << 5;
+                self.seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+            }
+        }
+    }
+}
+
+/// A deterministic pattern injected into the output of a [`Callback`][crate::Callback] with
+/// [`Callback::set_test_signal`][crate::Callback::set_test_signal], for automated tests which
+/// capture the output of the hardware and check it against the pattern.
+///
+/// Every pattern starts from its first frame whenever it is set, and is the same from run to run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TestSignal {
+    /// A single full-scale sample on every channel, repeated every `interval`, for measuring
+    /// latency and checking for dropped or repeated audio.
+    ImpulseTrain { interval: Duration },
+    /// A full-scale sine wave with the given frequency, in Hz, on every channel, for checking how
+    /// the chain handles signals at the edge of clipping.
+    Sine { frequency: f32 },
+    /// A half-scale sine wave on each channel in turn for `dwell`, starting from the first, with
+    /// every other channel silent. The tone on channel `n` is `500 * (n + 1)`Hz, so that the
+    /// channel can be identified from a capture even without knowing when the sweep started.
+    ChannelSweep { dwell: Duration },
+}
+
+/// Whether a [`TestSignal`] replaces the output of the engines or is mixed with it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InjectionMode {
+    /// Play only the test signal.
+    #[default]
+    Replace,
+    /// Add the test signal to whatever the engines are playing.
+    Mix,
+}
+
+/// A [`TestSignal`] along with how far through it playback is, owned by the callback.
+#[derive(Debug, Clone)]
+pub(crate) struct Injector {
+    signal: TestSignal,
+    /// The number of frames played since the signal was set.
+    pos: u64,
+}
+
+impl Injector {
+    pub(crate) fn new(signal: TestSignal) -> Self {
+        Self { signal, pos: 0 }
+    }
+
+    pub(crate) fn signal(&self) -> TestSignal {
+        self.signal
+    }
+
+    /// Replaces or mixes the next part of the signal into interleaved frames.
+    pub(crate) fn process(&mut self, frames: &mut [f32], channels: usize, mode: InjectionMode) {
+        for frame in frames.chunks_exact_mut(channels) {
+            for (channel, out) in frame.iter_mut().enumerate() {
+                let sample = self.sample(channel, channels);
+                *out = match mode {
+                    InjectionMode::Replace => sample,
+                    InjectionMode::Mix => *out + sample,
+                };
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Returns the sample of the current frame on the given channel.
+    // `is_multiple_of` would raise the MSRV to 1.87.
+    #[allow(clippy::manual_is_multiple_of)]
+    fn sample(&self, channel: usize, channels: usize) -> f32 {
+        let sine = |frequency: f64| {
+            // Working from the frame number, rather than a running phase, keeps it exact however
+            // long the signal plays.
+            let cycles = self.pos as f64 * frequency / SAMPLE_RATE as f64;
+            (cycles.fract() * std::f64::consts::TAU).sin() as f32
+        };
+        match self.signal {
+            TestSignal::ImpulseTrain { interval } => {
+                let interval = frames(interval);
+                f32::from(u8::from(self.pos % interval == 0))
+            }
+            TestSignal::Sine { frequency } => sine(f64::from(frequency)),
+            TestSignal::ChannelSweep { dwell } => {
+                let dwell = frames(dwell);
+                if (self.pos / dwell) % channels as u64 == channel as u64 {
+                    sine(500.0 * (channel + 1) as f64) * 0.5
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// Returns the number of frames in `time`, which is at least one.
+fn frames(time: Duration) -> u64 {
+    ((time.as_secs_f64() * SAMPLE_RATE as f64) as u64).max(1)
+}
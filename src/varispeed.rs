@@ -0,0 +1,57 @@
+//! Changing the playback rate of the engines attached to a [`Callback`][crate::Callback], and
+//! resampling its output to the rate the driver plays the device at.
+
+/// The slowest and fastest playback rates allowed.
+pub(crate) const MIN_RATE: f32 = 0.25;
+pub(crate) const MAX_RATE: f32 = 2.0;
+
+/// Resamples interleaved audio by a changeable factor, like changing the speed of a tape, so
+/// that both the tempo and pitch change.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Varispeed {
+    /// Source frames which haven't been played past yet.
+    source: Vec<f32>,
+    /// The position of the next output frame in `source`, in frames.
+    pos: f64,
+}
+
+impl Varispeed {
+    /// Returns whether no source frames are buffered, so that output at the normal rate can
+    /// skip resampling altogether.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.source.is_empty()
+    }
+
+    /// Forgets the buffered source frames, for when the number of channels changes.
+    pub(crate) fn clear(&mut self) {
+        self.source.clear();
+        self.pos = 0.0;
+    }
+
+    /// Fills `out` with interleaved frames played at `rate`, calling `render` to append
+    /// blocks of source frames to the given buffer whenever more are needed.
+    pub(crate) fn process(
+        &mut self,
+        out: &mut [f32],
+        channels: usize,
+        rate: f32,
+        mut render: impl FnMut(&mut Vec<f32>),
+    ) {
+        for frame in out.chunks_exact_mut(channels) {
+            let index = self.pos as usize;
+            while (index + 2) * channels > self.source.len() {
+                render(&mut self.source);
+            }
+            let frac = (self.pos - index as f64) as f32;
+            let (a, b) = self.source[index * channels..(index + 2) * channels].split_at(channels);
+            for ((out, &a), &b) in frame.iter_mut().zip(a).zip(b) {
+                *out = a + (b - a) * frac;
+            }
+            self.pos += rate as f64;
+        }
+
+        let consumed = (self.pos as usize).min(self.source.len() / channels);
+        self.source.drain(..consumed * channels);
+        self.pos -= consumed as f64;
+    }
+}
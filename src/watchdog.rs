@@ -0,0 +1,163 @@
+//! Noticing when a driver stops asking a playing device for audio.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    event::{self, EventSender},
+    AudioEvent,
+};
+
+/// How an [`SdlSound`][crate::SdlSound] watches for its device stalling, set with
+/// [`OpenOptions::watchdog`][crate::OpenOptions::watchdog].
+///
+/// Some drivers occasionally stop running the callback without reporting an error, for example
+/// when a Bluetooth stack wedges, so the game goes silent. A watchdog thread checks that the
+/// callback keeps running while the device is playing, and if it hasn't for `periods` buffer
+/// periods, sends [`AudioEvent::DeviceStalled`]. With `reopen`, the next
+/// [`SdlSound::update`][crate::SdlSound::update] then closes and reopens the device.
+/// # Example
+/// ```no_run
+/// use rg3d_sound_sdl::{OpenOptions, WatchdogPolicy};
+///
+/// let sdl = sdl2::init().unwrap();
+/// let audio = sdl.audio().unwrap();
+/// let mut sound = OpenOptions::new()
+///     .watchdog(WatchdogPolicy {
+///         reopen: true,
+///         ..Default::default()
+///     })
+///     .start_paused(false)
+///     .open(&audio)
+///     .unwrap();
+///
+/// // In the game loop:
+/// sound.update();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchdogPolicy {
+    /// The number of buffer periods without a callback after which the device is stalled.
+    pub periods: u32,
+    /// Whether to close and reopen a stalled device.
+    pub reopen: bool,
+}
+
+impl Default for WatchdogPolicy {
+    /// Reports a stall after 8 buffer periods, without reopening the device.
+    fn default() -> Self {
+        Self {
+            periods: 8,
+            reopen: false,
+        }
+    }
+}
+
+/// The state shared between an [`SdlSound`][crate::SdlSound] and its watchdog thread.
+struct Shared {
+    policy: WatchdogPolicy,
+    /// The number of callbacks run, counted by the callback itself.
+    heartbeat: Arc<AtomicU64>,
+    /// The length of a buffer period, in nanoseconds.
+    period: AtomicU64,
+    /// The name of the device, for events.
+    device: Mutex<Option<String>>,
+    /// Whether the device should be running callbacks.
+    playing: AtomicBool,
+    /// Set by the thread when the device stalls, until the stall is dealt with.
+    stalled: AtomicBool,
+}
+
+/// A handle to a watchdog thread, which stops once this is dropped.
+pub(crate) struct Watchdog {
+    shared: Arc<Shared>,
+}
+
+impl Watchdog {
+    /// Starts watching a device whose callback counts its runs in `heartbeat`, and whose buffer
+    /// takes `period` to play.
+    pub(crate) fn spawn(
+        policy: WatchdogPolicy,
+        heartbeat: Arc<AtomicU64>,
+        period: Duration,
+        device: Option<String>,
+        events: EventSender,
+    ) -> Self {
+        let shared = Arc::new(Shared {
+            policy,
+            heartbeat,
+            period: AtomicU64::new(period.as_nanos() as u64),
+            device: Mutex::new(device),
+            playing: AtomicBool::new(false),
+            stalled: AtomicBool::new(false),
+        });
+        let weak = Arc::downgrade(&shared);
+        thread::Builder::new()
+            .name("rg3d-sound-sdl watchdog".into())
+            .spawn(move || run(weak, events))
+            .expect("Failed to spawn watchdog thread");
+        Self { shared }
+    }
+
+    pub(crate) fn policy(&self) -> WatchdogPolicy {
+        self.shared.policy
+    }
+
+    /// Sets whether the device should be running callbacks.
+    pub(crate) fn set_playing(&self, playing: bool) {
+        self.shared.playing.store(playing, Ordering::Relaxed);
+    }
+
+    /// Starts watching another device, after the callback moved to it.
+    pub(crate) fn watch(&self, period: Duration, device: Option<String>) {
+        self.shared
+            .period
+            .store(period.as_nanos() as u64, Ordering::Relaxed);
+        *self.shared.device.lock().unwrap() = device;
+        self.shared.stalled.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns whether the device stalled since this was last called.
+    pub(crate) fn take_stalled(&self) -> bool {
+        self.shared.stalled.swap(false, Ordering::Relaxed)
+    }
+}
+
+fn run(shared: Weak<Shared>, events: EventSender) {
+    let mut last = None;
+    let mut missed = 0;
+    loop {
+        let period = match shared.upgrade() {
+            Some(shared) => Duration::from_nanos(shared.period.load(Ordering::Relaxed)),
+            None => return,
+        };
+        thread::sleep(period);
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+
+        let count = shared.heartbeat.load(Ordering::Relaxed);
+        if !shared.playing.load(Ordering::Relaxed) || last != Some(count) {
+            last = Some(count);
+            missed = 0;
+            continue;
+        }
+        missed += 1;
+        if missed == shared.policy.periods.max(1) {
+            shared.stalled.store(true, Ordering::Relaxed);
+            event::send(
+                &events,
+                AudioEvent::DeviceStalled {
+                    device: shared.device.lock().unwrap().clone(),
+                    silent_for: period * missed,
+                },
+            );
+        }
+    }
+}
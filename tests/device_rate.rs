@@ -0,0 +1,34 @@
+//! Renders a test tone through the whole pipeline to a device played at 48kHz, and checks that
+//! the output is resampled so that the tone keeps its pitch.
+
+use std::time::Duration;
+
+use rg3d_sound_sdl::{harness::Harness, OpenOptions};
+
+const DEVICE_RATE: u32 = 48_000;
+const FREQUENCY: f32 = 1000.0;
+
+#[test]
+fn tone_keeps_its_pitch() {
+    let sdl = sdl2::init().unwrap();
+    let audio = sdl.audio().unwrap();
+    let mut harness = Harness::open(&audio, &OpenOptions::new()).unwrap();
+    harness.set_device_rate(DEVICE_RATE);
+    harness.sound().with_callback(|callback| {
+        callback.play_test_tone(None, FREQUENCY, Duration::from_secs(2));
+    });
+
+    // A second at the device's rate holds a second of the tone.
+    let samples = harness.render(DEVICE_RATE as usize);
+    let left: Vec<_> = samples.chunks_exact(2).map(|frame| frame[0]).collect();
+    let cycles = left
+        .windows(2)
+        .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+        .count();
+    assert!(
+        (cycles as f32 - FREQUENCY).abs() <= 2.0,
+        "Played {} cycles in a second, not {}",
+        cycles,
+        FREQUENCY
+    );
+}
@@ -0,0 +1,50 @@
+//! Renders the fixed scene through the whole pipeline and compares it against the golden files in
+//! `tests/golden`. Run with `RG3D_SOUND_SDL_BLESS=1` to update them after an intended change.
+
+use rg3d_sound::context::SoundContext;
+use rg3d_sound_sdl::{
+    harness::{self, Harness},
+    ChannelLayout, MixMatrix, OpenOptions,
+};
+
+/// Two blocks, so that the second starts with the engine's state carried over from the first.
+const FRAMES: usize = 8208;
+const TOLERANCE: f32 = 1e-4;
+
+fn render(options: &OpenOptions, golden: &str, setup: impl FnOnce(&mut Harness)) {
+    let sdl = sdl2::init().unwrap();
+    let audio = sdl.audio().unwrap();
+    let mut harness = Harness::open(&audio, options).unwrap();
+    let context = SoundContext::new();
+    harness::build_scene(&context);
+    harness
+        .sound()
+        .engine()
+        .lock()
+        .unwrap()
+        .add_context(context);
+    setup(&mut harness);
+
+    let samples = harness.render(FRAMES);
+    let path = format!("{}/tests/golden/{}", env!("CARGO_MANIFEST_DIR"), golden);
+    harness::compare_golden(path, harness.spec(), &samples, TOLERANCE).unwrap();
+}
+
+// The dummy driver only has one device, so the scenes are rendered one after another.
+#[test]
+fn golden() {
+    render(&OpenOptions::new(), "stereo.wav", |harness| {
+        harness.sound().set_master_gain(0.8);
+        harness.sound().set_balance(0.25);
+    });
+    render(
+        OpenOptions::new().channel_layout(ChannelLayout::Surround51),
+        "surround51.wav",
+        |harness| {
+            harness.sound().with_callback(|callback| {
+                let id = callback.engine_ids().next().unwrap();
+                callback.set_routing(id, MixMatrix::upmix(ChannelLayout::Surround51));
+            });
+        },
+    );
+}
@@ -0,0 +1,97 @@
+//! Checks the SIMD paths against the scalar code they replace, at sizes which leave remainders
+//! for the scalar code to finish, and with samples which are NaN, infinite or out of range.
+
+use rg3d_sound_sdl::simd;
+
+/// Samples from -2 to 2, with the special values mixed in.
+fn samples(len: usize) -> Vec<f32> {
+    let mut seed = 0x2545_f491u32;
+    (0..len)
+        .map(|i| match i % 17 {
+            3 => f32::NAN,
+            7 => f32::INFINITY,
+            11 => f32::NEG_INFINITY,
+            13 => 250.0,
+            _ => {
+                // xorshift32
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as f32 / u32::MAX as f32 * 4.0 - 2.0
+            }
+        })
+        .collect()
+}
+
+fn same(a: f32, b: f32) -> bool {
+    a == b || (a.is_nan() && b.is_nan())
+}
+
+/// Whether two sums of the same products agree, allowing for them being added in another order.
+fn close(a: f32, b: f32) -> bool {
+    same(a, b) || (a - b).abs() <= 1e-5 * a.abs().max(b.abs()).max(1.0)
+}
+
+#[test]
+fn f32_to_i16() {
+    for len in [0, 1, 5, 7, 8, 9, 15, 17, 31, 100] {
+        let src = samples(len);
+        let (mut simd, mut scalar) = (vec![0; len], vec![0; len]);
+        simd::f32_to_i16(&src, &mut simd);
+        simd::scalar::f32_to_i16(&src, &mut scalar);
+        assert_eq!(simd, scalar, "{} samples", len);
+    }
+}
+
+#[test]
+fn ramp_gain() {
+    for channels in 1..=8 {
+        for frames in [0, 1, 3, 5, 7, 13, 33] {
+            for (start, end) in [(1.0, 1.0), (0.0, 1.0), (1.5, -0.5)] {
+                let mut simd = samples(frames * channels);
+                let mut scalar = simd.clone();
+                simd::ramp_gain(&mut simd, channels, start, end);
+                simd::scalar::ramp_gain(&mut scalar, channels, start, end);
+                for (i, (&a, &b)) in simd.iter().zip(&scalar).enumerate() {
+                    assert!(
+                        same(a, b),
+                        "{} channels, {} frames, from {} to {}: sample {} is {}, not {}",
+                        channels,
+                        frames,
+                        start,
+                        end,
+                        i,
+                        a,
+                        b
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn fold_to_stereo() {
+    for channels in [4, 6, 8] {
+        let gains: Vec<_> = (0..channels)
+            .map(|i| [i as f32 * 0.25 - 0.5, 1.0 - i as f32 * 0.125])
+            .collect();
+        for frames in [0, 1, 3, 5, 6, 7, 9, 13, 33] {
+            let from = samples(frames * channels);
+            let (mut simd, mut scalar) = (vec![(0.0, 0.0); frames], vec![(0.0, 0.0); frames]);
+            simd::fold_to_stereo(&from, &gains, &mut simd);
+            simd::scalar::fold_to_stereo(&from, &gains, &mut scalar);
+            for (i, (a, b)) in simd.iter().zip(&scalar).enumerate() {
+                assert!(
+                    close(a.0, b.0) && close(a.1, b.1),
+                    "{} channels, {} frames: frame {} is {:?}, not {:?}",
+                    channels,
+                    frames,
+                    i,
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Switches a playing device between SDL's dummy and disk drivers, which closes every open device,
+//! and checks that playback carries on.
+
+use std::{fs, path::Path, thread, time::Duration};
+
+use rg3d_sound_sdl::{DeviceSelector, OpenOptions};
+use sdl2::audio::AudioSpecDesired;
+
+fn len(path: &Path) -> u64 {
+    fs::metadata(path).map_or(0, |m| m.len())
+}
+
+#[test]
+fn switch_driver() {
+    let sdl = sdl2::init().unwrap();
+    let audio = sdl.audio().unwrap();
+    let mut sound = OpenOptions::new()
+        .start_paused(false)
+        .open_dummy(&audio)
+        .unwrap();
+
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("switch.raw");
+    let _ = fs::remove_file(&path);
+    sound
+        .switch_device(
+            DeviceSelector::Disk(path.clone()),
+            Duration::from_millis(100),
+        )
+        .unwrap();
+    assert_eq!(audio.current_audio_driver(), "disk");
+    sound.update();
+    assert!(sound.is_playing());
+    let written = len(&path);
+    thread::sleep(Duration::from_millis(300));
+    sound.update();
+    assert!(sound.is_playing());
+    assert!(
+        len(&path) > written,
+        "Nothing was played to the disk device"
+    );
+
+    // Switching would close the other device, so it's refused.
+    let other_path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("other.raw");
+    let desired = AudioSpecDesired {
+        freq: None,
+        channels: None,
+        samples: None,
+    };
+    let other = audio
+        .open_queue::<f32, _>(other_path.to_str(), &desired)
+        .unwrap();
+    assert!(sound
+        .switch_device(DeviceSelector::Dummy, Duration::ZERO)
+        .is_err());
+    let written = len(&path);
+    thread::sleep(Duration::from_millis(300));
+    sound.update();
+    assert!(sound.is_playing());
+    assert!(len(&path) > written, "The disk device stopped playing");
+    drop(other);
+
+    sound
+        .switch_device(DeviceSelector::Dummy, Duration::ZERO)
+        .unwrap();
+    assert_eq!(audio.current_audio_driver(), "dummy");
+    assert!(sound.is_playing());
+}
@@ -0,0 +1,52 @@
+//! Checks that repeating the audio before an underrun always fades out, whatever the decay.
+
+use std::panic;
+
+use rg3d_sound::engine::SoundEngine;
+use rg3d_sound_sdl::{BlockRenderer, Callback, UnderrunFill};
+use sdl2::audio::AudioCallback;
+
+/// Renders one block of a constant level, then panics on every block after it.
+struct Dies(bool);
+
+impl BlockRenderer for Dies {
+    fn render(&mut self, buf: &mut [(f32, f32)]) {
+        assert!(!self.0, "Renderer died");
+        self.0 = true;
+        buf.fill((0.5, 0.5));
+    }
+}
+
+#[test]
+fn repeat_fades_out() {
+    // The renderer panics on purpose, so the panics aren't worth printing.
+    panic::set_hook(Box::new(|_| {}));
+    for decay in [1.0, 1.5, f32::INFINITY, f32::NAN] {
+        let mut callback = Callback::new(Dies(false));
+        callback.set_underrun_fill(UnderrunFill::Repeat { decay });
+        let UnderrunFill::Repeat { decay: clamped } = callback.underrun_fill() else {
+            panic!("The fill changed kind");
+        };
+        assert!(
+            (0.0..1.0).contains(&clamped),
+            "{} was clamped to {}",
+            decay,
+            clamped
+        );
+
+        // A block at a time, so that each buffer is one repeat.
+        let mut buf = vec![0.0; SoundEngine::render_buffer_len() * 2];
+        callback.callback(&mut buf);
+        assert!(buf.iter().all(|&s| s == 0.5));
+        // Even the highest decay is inaudible after 688 repeats.
+        for _ in 0..700 {
+            callback.callback(&mut buf);
+        }
+        assert!(
+            buf.iter().all(|&s| s == 0.0),
+            "A decay of {} never faded out",
+            decay
+        );
+    }
+    let _ = panic::take_hook();
+}